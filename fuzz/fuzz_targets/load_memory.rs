@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../stb/tests/fuzz/mod.rs"]
+mod harness;
+
+fuzz_target!(|data: &[u8]| {
+    harness::load_memory(data);
+});
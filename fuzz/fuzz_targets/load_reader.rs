@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::panic;
+
+#[path = "../../stb/tests/fuzz/mod.rs"]
+mod harness;
+
+fuzz_target!(
+    init: {
+        // libfuzzer aborts on any panic, let the ones injected by the reader be caught
+        let default = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if info.payload().downcast_ref::<&str>() != Some(&harness::READER_PANIC) {
+                default(info);
+            }
+        }));
+    },
+    |data: &[u8]| {
+        harness::load_reader(data);
+    }
+);
@@ -19,6 +19,16 @@ static FILES: &[&str] = &[
 ];
 
 fn main() {
+    println!("cargo:rerun-if-changed=src/stb_alloc.h");
+
+    // Prefix of allocation functions exported to stb (see `src/alloc.rs`)
+    let version = env::var("CARGO_PKG_VERSION").unwrap();
+    let prefix = format!(
+        "rust_stb_sys_{}_",
+        version.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    );
+    println!("cargo:rustc-env=STB_SYS_PREFIX={}", prefix);
+
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     let bindings_path = out_dir.join("bindings.rs");
 
@@ -36,12 +46,14 @@ fn main() {
         .whitelist_function("stb.*")
         .whitelist_type("stb.*")
         .whitelist_var("stb.*")
+        .clang_arg(format!("-DSTB_SYS_PREFIX={}", prefix))
         .generate()
         .expect("Failed to generate bindings")
         .write_to_file(bindings_path)
         .expect("Failed to write bindings file");
 
     let mut builder = cc::Build::new();
+    builder.define("STB_SYS_PREFIX", prefix.as_str());
 
     #[cfg(feature = "stb_dxt")]
     {
@@ -66,6 +78,9 @@ fn main() {
         #[cfg(feature = "stbi_no_psd")]
         builder.define("STBI_NO_PSD", "1");
 
+        #[cfg(feature = "stbi_no_tga")]
+        builder.define("STBI_NO_TGA", "1");
+
         #[cfg(feature = "stbi_no_gif")]
         builder.define("STBI_NO_GIF", "1");
 
@@ -77,6 +92,33 @@ fn main() {
 
         #[cfg(feature = "stbi_no_pnm")]
         builder.define("STBI_NO_PNM", "1");
+
+        #[cfg(feature = "stbi_no_simd")]
+        builder.define("STBI_NO_SIMD", "1");
+
+        // NEON isn't enabled by default on ARM, as stb can't detect it at runtime.
+        // The feature is ignored on other targets, so it can be enabled unconditionally.
+        #[cfg(feature = "stbi_neon")]
+        {
+            let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+            if arch == "arm" || arch == "aarch64" {
+                builder.define("STBI_NEON", "1");
+            }
+        }
+
+        // Keeps zlib decoder with `stbi_no_png`
+        #[cfg(feature = "stbi_support_zlib")]
+        builder.define("STBI_SUPPORT_ZLIB", "1");
+
+        #[cfg(feature = "stbi_max_dimensions")]
+        {
+            // Hard backstop for image dimensions, stb refuses larger images with "too large"
+            println!("cargo:rerun-if-env-changed=STBI_MAX_DIMENSIONS");
+            let max = env::var("STBI_MAX_DIMENSIONS").unwrap_or_else(|_| "32768".to_string());
+            max.parse::<u32>()
+                .expect("STBI_MAX_DIMENSIONS must be an unsigned integer");
+            builder.define("STBI_MAX_DIMENSIONS", max.as_str());
+        }
     }
 
     builder.files(FILES).warnings(false).compile("libstb");
@@ -0,0 +1,358 @@
+//! Allocation functions used by stb instead of C `malloc`/`realloc`/`free` (see `stb_alloc.h`).
+//!
+//! The Rust allocator needs allocation layout on deallocation, while C API only passes a
+//! pointer, so layouts of blocks owned by stb are kept in a table keyed by block address.
+//! Blocks carry no header, so a block can be taken away from stb with [`take`] and handed
+//! over to Rust owners as is.
+//!
+//! The table is split into shards by address, so threads decoding in parallel rarely wait
+//! for each other. How blocks are allocated and reused is up to [`Hooks`] installed by callers.
+//!
+//! Symbols are exported with the crate version in their names (see `build.rs`), so different
+//! versions of this crate can be linked into one binary.
+
+use std::alloc::{self, Layout};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::os::raw::c_void;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Alignment of memory returned to stb, same as `malloc` guarantees on mainstream 64-bit
+/// platforms.
+pub const ALIGN: usize = 16;
+
+/// Number of table shards, a power of two
+const SHARDS: usize = 64;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const SHARD: Mutex<BTreeMap<usize, Layout>> = Mutex::new(BTreeMap::new());
+
+/// Layouts of blocks allocated for stb and not freed yet, keyed by address
+static BLOCKS: [Mutex<BTreeMap<usize, Layout>>; SHARDS] = [SHARD; SHARDS];
+
+thread_local! {
+    /// Hooks installed on this thread, set by [`with_hooks`]
+    static HOOKS: Cell<Option<Hooks>> = const { Cell::new(None) };
+}
+
+/// Returns the table shard holding block `p`
+fn shard(p: *mut c_void) -> MutexGuard<'static, BTreeMap<usize, Layout>> {
+    // Fibonacci hashing, so page aligned blocks are spread across shards too
+    let hash = ((p as usize / ALIGN) as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let index = (hash >> (64 - SHARDS.trailing_zeros())) as usize;
+    BLOCKS[index].lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn is_aligned(p: *mut u8) -> bool {
+    (p as usize & (ALIGN - 1)) == 0
+}
+
+fn hooks() -> Option<Hooks> {
+    HOOKS.try_with(Cell::get).ok().flatten()
+}
+
+/// Memory block along with the layout it was allocated with
+pub struct Block {
+    pub ptr: *mut u8,
+    pub layout: Layout,
+}
+
+impl Block {
+    /// Allocates a block of `size` bytes aligned for stb from the global allocator.
+    ///
+    /// The block is declared with `align` if it's lower than stb needs and the allocator
+    /// happens to return memory aligned for stb anyway, so `Vec` of elements with that
+    /// alignment is able to free it.
+    pub fn alloc(size: usize, align: usize) -> Option<Block> {
+        if align < ALIGN {
+            let layout = Layout::from_size_align(size.max(1), align).ok()?;
+            // SAFETY: layout size is non-zero
+            let ptr = unsafe { alloc::alloc(layout) };
+            if ptr.is_null() {
+                return None;
+            }
+            if is_aligned(ptr) {
+                return Some(Block { ptr, layout });
+            }
+
+            // Allocators are free to return less aligned memory than stb needs
+            unsafe { alloc::dealloc(ptr, layout) };
+        }
+
+        let layout = Layout::from_size_align(size.max(1), align.max(ALIGN)).ok()?;
+        let ptr = unsafe { alloc::alloc(layout) };
+        if ptr.is_null() {
+            None
+        } else {
+            Some(Block { ptr, layout })
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// Returns this block to the global allocator.
+    ///
+    /// # Safety
+    ///
+    /// The block must be allocated by the global allocator with its layout and not used
+    /// afterwards.
+    pub unsafe fn dealloc(self) {
+        alloc::dealloc(self.ptr, self.layout);
+    }
+
+    /// Hands this block over to stb
+    fn register(self) -> *mut c_void {
+        let p = self.ptr as *mut c_void;
+        shard(p).insert(p as usize, self.layout);
+        p
+    }
+}
+
+/// Allocation policy of a thread, see [`with_hooks`]
+#[derive(Copy, Clone)]
+pub struct Hooks {
+    /// Serves an allocation of `size` bytes, `None` leaves it to the global allocator
+    pub alloc: fn(size: usize) -> Option<Block>,
+    /// Takes back a block freed by stb, a returned block goes back to the global allocator
+    pub free: fn(block: Block) -> Option<Block>,
+}
+
+/// Runs `f` with `hooks` serving stb allocations made on the current thread.
+///
+/// Blocks are moved rather than reallocated in place while hooks are installed, as they may
+/// come from elsewhere than the global allocator.
+///
+/// # Safety
+///
+/// Blocks served by `hooks` that aren't allocated by the global allocator with their layout
+/// must not be returned by `free`, and must be freed by stb or [`take`]n before `f` returns.
+pub unsafe fn with_hooks<R>(hooks: Hooks, f: impl FnOnce() -> R) -> R {
+    /// Restores the previous hooks, even if `f` panics
+    struct Guard(Option<Hooks>);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            HOOKS.with(|hooks| hooks.set(self.0));
+        }
+    }
+
+    let _guard = Guard(HOOKS.with(|current| current.replace(Some(hooks))));
+    f()
+}
+
+/// Returns the layout of block `p` allocated for stb, `None` if it's unknown
+pub fn layout(p: *mut c_void) -> Option<Layout> {
+    shard(p).get(&(p as usize)).copied()
+}
+
+/// Takes block `p` away from stb: it's no longer tracked, so freeing it with [`free`] does
+/// nothing and the caller becomes responsible for it. Returns `None` if `p` is unknown.
+pub fn take(p: *mut c_void) -> Option<Block> {
+    let layout = shard(p).remove(&(p as usize))?;
+    Some(Block {
+        ptr: p as *mut u8,
+        layout,
+    })
+}
+
+/// Allocates `size` bytes, same as C `malloc`
+///
+/// # Safety
+///
+/// Returned memory must be freed with [`free`] only.
+#[export_name = concat!(env!("STB_SYS_PREFIX"), "malloc")]
+pub unsafe extern "C" fn malloc(size: usize) -> *mut c_void {
+    let block = hooks()
+        .and_then(|hooks| (hooks.alloc)(size))
+        .or_else(|| Block::alloc(size, ALIGN));
+
+    match block {
+        Some(block) => block.register(),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Resizes block `p` to `new_size` bytes, same as C `realloc`
+///
+/// # Safety
+///
+/// `p` must be null or returned by [`malloc`] or [`realloc`] and not freed yet.
+#[export_name = concat!(env!("STB_SYS_PREFIX"), "realloc")]
+pub unsafe extern "C" fn realloc(p: *mut c_void, new_size: usize) -> *mut c_void {
+    if p.is_null() {
+        return malloc(new_size);
+    }
+
+    let layout = match layout(p) {
+        Some(layout) => layout,
+        None => return ptr::null_mut(),
+    };
+    if layout.size() >= new_size {
+        return p;
+    }
+
+    // The allocator only keeps the declared alignment, so blocks declared with a weaker one
+    // than stb needs are moved as well
+    if hooks().is_some() || layout.align() < ALIGN {
+        let new = malloc(new_size);
+        // Same as C, the original block is left untouched on failure
+        if !new.is_null() {
+            ptr::copy_nonoverlapping(p as *const u8, new as *mut u8, layout.size());
+            free(p);
+        }
+        return new;
+    }
+
+    let new_layout = match Layout::from_size_align(new_size, layout.align()) {
+        Ok(layout) => layout,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let new = alloc::realloc(p as *mut u8, layout, new_size);
+    if new.is_null() {
+        return ptr::null_mut();
+    }
+
+    take(p);
+    Block {
+        ptr: new,
+        layout: new_layout,
+    }
+    .register()
+}
+
+/// Frees block `p`, same as C `free`. Unknown pointers are ignored rather than handed to the
+/// allocator.
+///
+/// # Safety
+///
+/// `p` must not be used afterwards.
+#[export_name = concat!(env!("STB_SYS_PREFIX"), "free")]
+pub unsafe extern "C" fn free(p: *mut c_void) {
+    if p.is_null() {
+        return;
+    }
+
+    let block = match (take(p), hooks()) {
+        (Some(block), Some(hooks)) => (hooks.free)(block),
+        (block, None) => block,
+        (None, _) => None,
+    };
+
+    if let Some(block) = block {
+        block.dealloc();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn malloc_realloc_free() {
+        unsafe {
+            let p = malloc(3) as *mut u8;
+            assert!(!p.is_null());
+            assert_eq!(p as usize % ALIGN, 0);
+
+            ptr::copy_nonoverlapping([1_u8, 2, 3].as_ptr(), p, 3);
+
+            let p = realloc(p as *mut c_void, 4096) as *mut u8;
+            assert!(!p.is_null());
+            assert_eq!(p as usize % ALIGN, 0);
+            assert_eq!(std::slice::from_raw_parts(p, 3), &[1, 2, 3]);
+
+            let p = realloc(p as *mut c_void, 2) as *mut u8;
+            assert_eq!(std::slice::from_raw_parts(p, 2), &[1, 2]);
+
+            free(p as *mut c_void);
+        }
+    }
+
+    #[test]
+    fn null_pointers() {
+        unsafe {
+            free(ptr::null_mut());
+
+            let p = realloc(ptr::null_mut(), 8);
+            assert!(!p.is_null());
+            free(p);
+
+            assert!(malloc(usize::MAX).is_null());
+        }
+    }
+
+    #[test]
+    fn take_blocks() {
+        unsafe {
+            let p = malloc(100);
+            assert_eq!(layout(p).map(|layout| layout.size()), Some(100));
+
+            let block = take(p).expect("Block must be known");
+            assert!(layout(p).is_none());
+            assert!(take(p).is_none());
+
+            // Taken blocks are ignored by stb
+            free(p);
+            block.dealloc();
+        }
+    }
+
+    #[test]
+    fn hooks_serve_blocks() {
+        thread_local! {
+            static FREED: Cell<usize> = const { Cell::new(0) };
+        }
+
+        let hooks = Hooks {
+            alloc: |size| Block::alloc(size * 2, 1),
+            free: |block| {
+                FREED.with(|freed| freed.set(freed.get() + 1));
+                Some(block)
+            },
+        };
+
+        unsafe {
+            with_hooks(hooks, || {
+                let p = malloc(10);
+                assert_eq!(layout(p).map(|layout| layout.size()), Some(20));
+
+                // Blocks are moved on growth, so hooks serve the new one
+                let p = realloc(p, 30);
+                assert_eq!(layout(p).map(|layout| layout.size()), Some(60));
+                assert_eq!(FREED.with(Cell::get), 1);
+
+                free(p);
+                assert_eq!(FREED.with(Cell::get), 2);
+            });
+
+            // Hooks are removed once the scope ends
+            let p = malloc(10);
+            assert_eq!(layout(p).map(|layout| layout.size()), Some(10));
+            free(p);
+            assert_eq!(FREED.with(Cell::get), 2);
+        }
+    }
+
+    #[test]
+    fn blocks_freed_on_other_threads() {
+        let blocks = (0..64)
+            .map(|size| unsafe { malloc(size * 1000) } as usize)
+            .collect::<Vec<_>>();
+
+        thread::spawn(move || {
+            for p in blocks {
+                let p = p as *mut c_void;
+                assert!(layout(p).is_some());
+                unsafe { free(p) };
+                assert!(layout(p).is_none());
+            }
+        })
+        .join()
+        .unwrap();
+    }
+}
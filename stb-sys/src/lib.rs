@@ -6,3 +6,5 @@
 #![allow(clippy::excessive_precision)]
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+pub mod alloc;
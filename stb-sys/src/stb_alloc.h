@@ -0,0 +1,23 @@
+#ifndef STB_ALLOC_H
+#define STB_ALLOC_H
+
+#include <stddef.h>
+
+// Allocation functions implemented in Rust (see src/alloc.rs), so memory allocated by stb
+// goes through the Rust global allocator.
+//
+// Exported names start with STB_SYS_PREFIX, which build.rs derives from the crate version,
+// so different versions of the crate can be linked into one binary.
+
+#define STB_ALLOC_CONCAT(prefix, name) prefix##name
+#define STB_ALLOC_NAME(prefix, name) STB_ALLOC_CONCAT(prefix, name)
+
+#define rust_stb_malloc STB_ALLOC_NAME(STB_SYS_PREFIX, malloc)
+#define rust_stb_realloc STB_ALLOC_NAME(STB_SYS_PREFIX, realloc)
+#define rust_stb_free STB_ALLOC_NAME(STB_SYS_PREFIX, free)
+
+void *rust_stb_malloc(size_t size);
+void *rust_stb_realloc(void *p, size_t newsz);
+void rust_stb_free(void *p);
+
+#endif
@@ -1,3 +1,8 @@
+#include "stb_alloc.h"
+
+#define STBI_MALLOC(sz) rust_stb_malloc(sz)
+#define STBI_REALLOC(p, newsz) rust_stb_realloc(p, newsz)
+#define STBI_FREE(p) rust_stb_free(p)
 
 #define STBI_NO_STDIO
 #define STB_IMAGE_IMPLEMENTATION
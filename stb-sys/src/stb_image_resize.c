@@ -1,2 +1,7 @@
+#include "stb_alloc.h"
+
+#define STBIR_MALLOC(size, c) ((void)(c), rust_stb_malloc(size))
+#define STBIR_FREE(ptr, c) ((void)(c), rust_stb_free(ptr))
+
 #define STB_IMAGE_RESIZE_IMPLEMENTATION
 #include "../vendor/stb/stb_image_resize.h"
\ No newline at end of file
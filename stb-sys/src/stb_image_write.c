@@ -1,2 +1,13 @@
+#include "stb_alloc.h"
+
+#define STBIW_MALLOC(sz) rust_stb_malloc(sz)
+#define STBIW_REALLOC(p, newsz) rust_stb_realloc(p, newsz)
+#define STBIW_FREE(p) rust_stb_free(p)
+
 #define STB_IMAGE_WRITE_IMPLEMENTATION
 #include "../vendor/stb/stb_image_write.h"
+
+// Memory returned by stbi_zlib_compress is allocated with STBIW_MALLOC, so release it with the matching function.
+void stbi_write_free(void *p) {
+    STBIW_FREE(p);
+}
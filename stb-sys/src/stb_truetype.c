@@ -1,2 +1,7 @@
+#include "stb_alloc.h"
+
+#define STBTT_malloc(x, u) ((void)(u), rust_stb_malloc(x))
+#define STBTT_free(x, u) ((void)(u), rust_stb_free(x))
+
 #define STB_TRUETYPE_IMPLEMENTATION
 #include "../vendor/stb/stb_truetype.h"
\ No newline at end of file
@@ -0,0 +1,126 @@
+//! Decoding benchmarks, run with `cargo bench -p stb --bench image`.
+//!
+//! Fixtures are 256x256 images from `tests/fixtures/bench`. Enable `stbi_no_simd` (or
+//! `stbi_neon` on ARM) to compare decoders with and without SIMD paths.
+
+// Every benchmarked format might be disabled, e.g. with `--all-features`
+#![cfg_attr(
+    all(
+        feature = "stbi_no_jpeg",
+        feature = "stbi_no_png",
+        feature = "stbi_no_gif",
+        any(feature = "stbi_no_hdr", feature = "stbi_no_linear")
+    ),
+    allow(dead_code, unused_imports)
+)]
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, Criterion, Throughput};
+use stb::image::{Channels, LoadOptions};
+
+fn fixture(file: &str) -> Vec<u8> {
+    common::fixture(&format!("bench/{}", file))
+}
+
+/// Benchmarks decoding of `file` from memory and from reader, `from_memory` and `from_reader`
+/// must decode the whole image
+fn bench_decode<M, R>(
+    group: &mut BenchmarkGroup<'_, criterion::measurement::WallTime>,
+    file: &str,
+    from_memory: impl Fn(&[u8]) -> M,
+    from_reader: impl Fn(&mut &[u8]) -> R,
+) {
+    let data = fixture(file);
+    group.throughput(Throughput::Bytes(data.len() as u64));
+
+    group.bench_function("memory", |b| b.iter(|| from_memory(&data)));
+    group.bench_function("reader", |b| b.iter(|| from_reader(&mut &data[..])));
+}
+
+#[cfg(not(feature = "stbi_no_jpeg"))]
+fn jpeg(c: &mut Criterion) {
+    use stb::image::{stbi_load_from_memory, stbi_load_from_reader};
+
+    for file in ["baseline.jpg", "progressive.jpg"] {
+        let mut group = c.benchmark_group(format!("jpeg/{}", file));
+        bench_decode(
+            &mut group,
+            file,
+            |data| stbi_load_from_memory(data, Channels::Rgb, LoadOptions::default()).unwrap(),
+            |reader| stbi_load_from_reader(reader, Channels::Rgb, LoadOptions::default()).unwrap(),
+        );
+        group.finish();
+    }
+}
+
+#[cfg(not(feature = "stbi_no_png"))]
+fn png(c: &mut Criterion) {
+    use stb::image::{
+        stbi_load_16_from_memory, stbi_load_16_from_reader, stbi_load_from_memory,
+        stbi_load_from_reader,
+    };
+
+    let mut group = c.benchmark_group("png/rgb8.png");
+    bench_decode(
+        &mut group,
+        "rgb8.png",
+        |data| stbi_load_from_memory(data, Channels::Rgb, LoadOptions::default()).unwrap(),
+        |reader| stbi_load_from_reader(reader, Channels::Rgb, LoadOptions::default()).unwrap(),
+    );
+    group.finish();
+
+    let mut group = c.benchmark_group("png/rgb16.png");
+    bench_decode(
+        &mut group,
+        "rgb16.png",
+        |data| stbi_load_16_from_memory(data, Channels::Rgb, LoadOptions::default()).unwrap(),
+        |reader| stbi_load_16_from_reader(reader, Channels::Rgb, LoadOptions::default()).unwrap(),
+    );
+    group.finish();
+}
+
+#[cfg(all(not(feature = "stbi_no_hdr"), not(feature = "stbi_no_linear")))]
+fn hdr(c: &mut Criterion) {
+    use stb::image::{stbi_loadf_from_memory, stbi_loadf_from_reader};
+
+    let mut group = c.benchmark_group("hdr/image.hdr");
+    bench_decode(
+        &mut group,
+        "image.hdr",
+        |data| stbi_loadf_from_memory(data, Channels::Rgb, LoadOptions::default()).unwrap(),
+        |reader| stbi_loadf_from_reader(reader, Channels::Rgb, LoadOptions::default()).unwrap(),
+    );
+    group.finish();
+}
+
+#[cfg(not(feature = "stbi_no_gif"))]
+fn gif(c: &mut Criterion) {
+    use stb::image::{stbi_load_from_reader, stbi_load_gif_from_memory};
+
+    // stb decodes all frames from memory only, so reader decodes the first frame
+    let mut group = c.benchmark_group("gif/animated.gif");
+    bench_decode(
+        &mut group,
+        "animated.gif",
+        |data| stbi_load_gif_from_memory(data, Channels::RgbAlpha, LoadOptions::default()).unwrap(),
+        |reader| stbi_load_from_reader(reader, Channels::RgbAlpha, LoadOptions::default()).unwrap(),
+    );
+    group.finish();
+}
+
+#[allow(unused_variables)]
+fn benches(c: &mut Criterion) {
+    #[cfg(not(feature = "stbi_no_jpeg"))]
+    jpeg(c);
+    #[cfg(not(feature = "stbi_no_png"))]
+    png(c);
+    #[cfg(all(not(feature = "stbi_no_hdr"), not(feature = "stbi_no_linear")))]
+    hdr(c);
+    #[cfg(not(feature = "stbi_no_gif"))]
+    gif(c);
+}
+
+criterion_group!(decode, benches);
+criterion_main!(decode);
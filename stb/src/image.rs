@@ -21,18 +21,158 @@
 //!
 //! Rust implementation notes:
 //!
-//! -  The crate wraps `stbi_io_callbacks` with a generic reader (anything that implements `io::Read` and `io::Seek`).
+//! -  The crate wraps `stbi_io_callbacks` with a generic reader (anything that implements `io::Read`,
+//! seeking is not required).
 //! So look for `stbi_xyz_from_reader` APIs instead of `stbi_xyz_from_callbacks`.
-//! -  There is no `Stdio` version of the API since it is convenient enough to use `stbi_xyz_from_reader`
-//! API from Rust and there is no need to pay C string conversion overhead.
+//! -  There is no `Stdio` version of the API, use [`load_path`] instead, which reads files with `std::fs`
+//! and has no C string conversion overhead.
 //! - You can use `stbi_no_FORMAT` feature toggles to disable not needed image formats.
+//! - Loaders return `Result` with an [`Error`] built from `stbi_failure_reason` (or the original
+//! `io::Error` when a reader fails) instead of a null pointer.
+//! - A reader panic aborts decoding and is resumed once stb returns, it never unwinds through C.
 
 use stb_sys as sys;
+use std::any::Any;
 use std::cmp::Ordering;
+use std::error;
 use std::ffi;
+use std::fmt;
+use std::fs;
 use std::io;
+use std::mem;
+use std::ops;
 use std::os::raw;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
 use std::slice;
+use std::sync::{PoisonError, RwLock};
+
+mod alloc;
+#[cfg(feature = "async")]
+mod async_io;
+#[cfg(feature = "rayon")]
+pub mod batch;
+mod chunks;
+mod color;
+pub mod cubemap;
+mod exif;
+mod format;
+#[cfg(feature = "image-interop")]
+mod interop;
+#[cfg(test)]
+mod test_util;
+#[cfg(not(feature = "stbi_no_png"))]
+mod text;
+mod tonemap;
+
+#[cfg(feature = "async")]
+pub use async_io::{info_async, load_async};
+pub use color::{color_info_from_memory, Chromaticities, ColorInfo, RenderingIntent};
+pub use exif::{jpeg_orientation, Orientation};
+pub use format::{detect_format, detect_format_from_reader, HeaderReader, ImageFormat};
+#[cfg(feature = "image-interop")]
+pub use interop::StbDecoder;
+#[cfg(not(feature = "stbi_no_png"))]
+pub use text::{png_text_from_memory, TextChunk};
+pub use tonemap::{HdrToLdr, Tonemap};
+
+/// Errors reported by image loaders.
+///
+/// Decoder failures are built from `stbi_failure_reason`, reader failures carry the original
+/// `io::Error` returned by the underlying reader.
+#[derive(Debug)]
+pub enum Error {
+    /// Image type is not recognized (or support for it is disabled via `stbi_no_*` features)
+    UnknownFormat,
+    /// Image data is malformed or truncated, holds the stb failure reason
+    Corrupt(&'static str),
+    /// Image uses a feature of the format stb doesn't support, holds the stb failure reason
+    Unsupported(&'static str),
+    /// stb failed to allocate memory for the image
+    OutOfMemory,
+    /// Image dimensions exceed what stb is able to decode
+    TooLarge,
+    /// Image dimensions or decoded size exceed configured [`Limits`], or compressed metadata
+    /// expands past its built-in cap
+    LimitsExceeded,
+    /// Destination buffer can't hold the decoded image, holds the number of required elements
+    BufferTooSmall(usize),
+    /// The reader returned an error
+    Io(io::Error),
+}
+
+impl Error {
+    /// Builds an error from the reason of the last failure reported by stb on this thread
+    fn from_failure_reason() -> Self {
+        Error::from_reason(failure_reason())
+    }
+
+    fn from_reason(reason: &'static str) -> Self {
+        match reason {
+            "unknown image type" => Error::UnknownFormat,
+            "outofmem" => Error::OutOfMemory,
+            "too large" => Error::TooLarge,
+            _ if reason.contains("not supported")
+                || reason.contains("unsupported")
+                || reason.contains("only")
+                || reason == "progressive jpeg"
+                || reason == "BMP RLE"
+                || reason == "monochrome" =>
+            {
+                Error::Unsupported(reason)
+            }
+            _ => Error::Corrupt(reason),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownFormat => write!(f, "unknown image format"),
+            Error::Corrupt(reason) => write!(f, "corrupt image: {}", reason),
+            Error::Unsupported(reason) => write!(f, "unsupported image: {}", reason),
+            Error::OutOfMemory => write!(f, "out of memory"),
+            Error::TooLarge => write!(f, "image is too large"),
+            Error::LimitsExceeded => write!(f, "image exceeds configured limits"),
+            Error::BufferTooSmall(required) => {
+                write!(f, "buffer is too small, {} elements required", required)
+            }
+            Error::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Returns the reason of the last failure reported by stb on this thread
+pub(crate) fn failure_reason() -> &'static str {
+    let reason = unsafe { sys::stbi_failure_reason() };
+    if reason.is_null() {
+        return "unknown error";
+    }
+
+    // stb keeps failure reasons as string literals, so it's safe to keep them around
+    unsafe { ffi::CStr::from_ptr(reason) }
+        .to_str()
+        .unwrap_or("unknown error")
+}
+
+/// A specialized `Result` type for image loaders
+pub type Result<T> = std::result::Result<T, Error>;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
@@ -54,41 +194,367 @@ pub struct Info {
     pub components: i32,
 }
 
-/// Holds image memory allocated by stb and responsible for calling `stbi_image_free` once dropped.
-pub struct Data<T> {
-    data: *mut T,
-    size: usize,
+/// Upper bounds on image dimensions and decoded size, guards against decompression bombs.
+///
+/// When any limit is set, loaders probe image dimensions first and refuse to decode images
+/// exceeding the limits with `Error::LimitsExceeded`. `None` means no limit.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Limits {
+    /// Maximum image width in pixels
+    pub max_width: Option<u32>,
+    /// Maximum image height in pixels
+    pub max_height: Option<u32>,
+    /// Maximum number of pixels (width x height)
+    pub max_pixels: Option<u64>,
+    /// Maximum size of decoded image in bytes
+    pub max_alloc_bytes: Option<u64>,
 }
 
-impl<T> Data<T> {
-    fn new(data: *mut T, desired_channels: Channels, info: Info) -> Self {
-        let components = if desired_channels == Channels::Default {
+impl Limits {
+    fn is_unlimited(&self) -> bool {
+        *self == Limits::default()
+    }
+
+    /// Checks image with `info` decoded into `channels` of `T` against the limits
+    fn check<T>(&self, info: Info, desired_channels: Channels) -> Result<()> {
+        self.check_frames::<T>(info, desired_channels, 1)
+    }
+
+    /// Checks `frames` of image with `info` against the limits, dimension limits apply to each
+    /// frame and the size limit applies to all of them
+    fn check_frames<T>(&self, info: Info, desired_channels: Channels, frames: u64) -> Result<()> {
+        let channels = if desired_channels == Channels::Default {
             info.components
         } else {
             desired_channels as i32
         };
 
-        let size = (info.width * info.height * components) as usize;
+        let width = info.width.max(0) as u64;
+        let height = info.height.max(0) as u64;
+        let pixels = width * height;
+        let bytes = pixels
+            .saturating_mul(channels.max(0) as u64)
+            .saturating_mul(mem::size_of::<T>() as u64)
+            .saturating_mul(frames);
+
+        let exceeds = |value: u64, limit: Option<u64>| limit.is_some_and(|limit| value > limit);
+
+        if exceeds(width, self.max_width.map(u64::from))
+            || exceeds(height, self.max_height.map(u64::from))
+            || exceeds(pixels, self.max_pixels)
+            || exceeds(bytes, self.max_alloc_bytes)
+        {
+            Err(Error::LimitsExceeded)
+        } else {
+            Ok(())
+        }
+    }
 
+    /// Probes image in `buffer` and checks it against the limits
+    fn check_memory<T>(&self, buffer: &[u8], desired_channels: Channels) -> Result<()> {
+        if self.is_unlimited() {
+            return Ok(());
+        }
+
+        let info = stbi_info_from_memory(buffer)?;
+        self.check::<T>(info, desired_channels)
+    }
+
+    /// Probes GIF image in `buffer` and checks all of its frames against the limits.
+    /// stb keeps all frames in memory composited as RGBA, before converting them into
+    /// `desired_channels`, so that's the size checked.
+    #[cfg(not(feature = "stbi_no_gif"))]
+    fn check_gif(&self, buffer: &[u8]) -> Result<()> {
+        if self.is_unlimited() {
+            return Ok(());
+        }
+
+        let info = stbi_info_from_memory(buffer)?;
+        let frames = chunks::gif_frame_count(buffer).unwrap_or(1).max(1);
+        self.check_frames::<u8>(info, Channels::RgbAlpha, frames as u64)
+    }
+
+    /// Probes image from `reader` and checks it against the limits, `reader` is rewound after
+    fn check_reader<T, R: io::Read>(
+        &self,
+        reader: &mut Wrapper<R>,
+        callbacks: &sys::stbi_io_callbacks,
+        desired_channels: Channels,
+    ) -> Result<()> {
+        if self.is_unlimited() {
+            return Ok(());
+        }
+
+        let info = reader.probe(callbacks)?;
+        self.check::<T>(info, desired_channels)
+    }
+}
+
+/// Decoding options applied to a single load call.
+///
+/// stb keeps these switches in global state, the loaders apply them on the calling thread only
+/// (using stb's thread local variants) and reset them to defaults once decoding is done, so
+/// concurrent loads with different options don't affect each other.
+/// HDR <-> LDR conversion parameters have no thread local variants in stb, so loads with
+/// non default conversion parameters are serialized with an internal lock.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LoadOptions {
+    flip_vertically: bool,
+    unpremultiply: bool,
+    convert_iphone_png_to_rgb: bool,
+    apply_orientation: bool,
+    hdr_to_ldr_gamma: f32,
+    hdr_to_ldr_scale: f32,
+    ldr_to_hdr_gamma: f32,
+    ldr_to_hdr_scale: f32,
+    hdr_to_ldr: Option<HdrToLdr>,
+    limits: Limits,
+}
+
+const DEFAULT_GAMMA: f32 = 2.2;
+const DEFAULT_SCALE: f32 = 1.0;
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            flip_vertically: false,
+            unpremultiply: false,
+            convert_iphone_png_to_rgb: false,
+            apply_orientation: false,
+            hdr_to_ldr_gamma: DEFAULT_GAMMA,
+            hdr_to_ldr_scale: DEFAULT_SCALE,
+            ldr_to_hdr_gamma: DEFAULT_GAMMA,
+            ldr_to_hdr_scale: DEFAULT_SCALE,
+            hdr_to_ldr: None,
+            limits: Limits::default(),
+        }
+    }
+}
+
+/// Guards stb's global HDR <-> LDR conversion parameters
+static CONVERSION: RwLock<()> = RwLock::new(());
+
+impl LoadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flip the image vertically, so the first pixel in the output array is the bottom left
+    pub fn flip_vertically(mut self, true_if_should_flip: bool) -> Self {
+        self.flip_vertically = true_if_should_flip;
+        self
+    }
+
+    /// Force a divide per pixel to remove any premultiplied alpha *only* if the image file
+    /// explicitly says there's premultiplied data (currently only happens in iPhone images,
+    /// and only if iPhone convert-to-rgb processing is on).
+    pub fn unpremultiply(mut self, true_if_should_unpremultiply: bool) -> Self {
+        self.unpremultiply = true_if_should_unpremultiply;
+        self
+    }
+
+    /// Convert iphone-formatted PNGs back to RGB, even though they are internally encoded
+    /// differently. Otherwise you will get the native iphone "format" through (which is BGR
+    /// stored in RGB).
+    pub fn convert_iphone_png_to_rgb(mut self, true_if_should_convert: bool) -> Self {
+        self.convert_iphone_png_to_rgb = true_if_should_convert;
+        self
+    }
+
+    /// Rotate and flip decoded pixels of JPEG images according to their EXIF orientation, so
+    /// the image is upright. stb ignores EXIF metadata, so it's off by default.
+    pub fn apply_orientation(mut self, true_if_should_apply: bool) -> Self {
+        self.apply_orientation = true_if_should_apply;
+        self
+    }
+
+    /// Gamma used when an HDR image is loaded with an 8 or 16-bit loader (2.2 by default)
+    pub fn hdr_to_ldr_gamma(mut self, gamma: f32) -> Self {
+        self.hdr_to_ldr_gamma = gamma;
+        self
+    }
+
+    /// Scale used when an HDR image is loaded with an 8 or 16-bit loader (1.0 by default)
+    pub fn hdr_to_ldr_scale(mut self, scale: f32) -> Self {
+        self.hdr_to_ldr_scale = scale;
+        self
+    }
+
+    /// Gamma used when an LDR image is loaded with a float loader (2.2 by default)
+    pub fn ldr_to_hdr_gamma(mut self, gamma: f32) -> Self {
+        self.ldr_to_hdr_gamma = gamma;
+        self
+    }
+
+    /// Scale used when an LDR image is loaded with a float loader (1.0 by default)
+    pub fn ldr_to_hdr_scale(mut self, scale: f32) -> Self {
+        self.ldr_to_hdr_scale = scale;
+        self
+    }
+
+    /// Convert HDR images loaded with an 8-bit loader with `conversion` instead of stb's gamma
+    /// curve, which makes `hdr_to_ldr_gamma` and `hdr_to_ldr_scale` unused.
+    /// HDR images are decoded as float first, so limits account for 4 bytes per sample.
+    /// Has no effect with `stbi_no_linear`, which disables float decoding.
+    pub fn hdr_to_ldr(mut self, conversion: HdrToLdr) -> Self {
+        self.hdr_to_ldr = Some(conversion);
+        self
+    }
+
+    /// Refuse to decode images exceeding `limits`
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    fn has_default_conversion(&self) -> bool {
+        self.hdr_to_ldr_gamma == DEFAULT_GAMMA
+            && self.hdr_to_ldr_scale == DEFAULT_SCALE
+            && self.ldr_to_hdr_gamma == DEFAULT_GAMMA
+            && self.ldr_to_hdr_scale == DEFAULT_SCALE
+    }
+
+    /// Runs `f` with these options applied, resets stb state to defaults afterwards
+    fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _thread = ThreadScope::new(self);
+
+        if self.has_default_conversion() {
+            let _lock = CONVERSION.read().unwrap_or_else(PoisonError::into_inner);
+            f()
+        } else {
+            let _lock = CONVERSION.write().unwrap_or_else(PoisonError::into_inner);
+            let _conversion = ConversionScope::new(self);
+            f()
+        }
+    }
+}
+
+/// Applies thread local options and resets them once dropped
+struct ThreadScope;
+
+impl ThreadScope {
+    fn new(options: &LoadOptions) -> Self {
+        Self::apply(
+            options.flip_vertically,
+            options.unpremultiply,
+            options.convert_iphone_png_to_rgb,
+        );
+        ThreadScope
+    }
+
+    fn apply(flip: bool, unpremultiply: bool, convert: bool) {
+        unsafe {
+            sys::stbi_set_flip_vertically_on_load_thread(flip as _);
+            sys::stbi_set_unpremultiply_on_load_thread(unpremultiply as _);
+            sys::stbi_convert_iphone_png_to_rgb_thread(convert as _);
+        }
+    }
+}
+
+impl Drop for ThreadScope {
+    fn drop(&mut self) {
+        Self::apply(false, false, false);
+    }
+}
+
+/// Applies global conversion parameters and resets them once dropped.
+/// Must only be used while holding `CONVERSION` write lock.
+struct ConversionScope;
+
+impl ConversionScope {
+    fn new(options: &LoadOptions) -> Self {
+        Self::apply(
+            options.hdr_to_ldr_gamma,
+            options.hdr_to_ldr_scale,
+            options.ldr_to_hdr_gamma,
+            options.ldr_to_hdr_scale,
+        );
+        ConversionScope
+    }
+
+    #[allow(unused_variables)]
+    fn apply(
+        hdr_to_ldr_gamma: f32,
+        hdr_to_ldr_scale: f32,
+        ldr_to_hdr_gamma: f32,
+        ldr_to_hdr_scale: f32,
+    ) {
+        #[cfg(not(feature = "stbi_no_hdr"))]
+        unsafe {
+            sys::stbi_hdr_to_ldr_gamma(hdr_to_ldr_gamma);
+            sys::stbi_hdr_to_ldr_scale(hdr_to_ldr_scale);
+        }
+
+        #[cfg(not(feature = "stbi_no_linear"))]
+        unsafe {
+            sys::stbi_ldr_to_hdr_gamma(ldr_to_hdr_gamma);
+            sys::stbi_ldr_to_hdr_scale(ldr_to_hdr_scale);
+        }
+    }
+}
+
+impl Drop for ConversionScope {
+    fn drop(&mut self) {
+        Self::apply(DEFAULT_GAMMA, DEFAULT_SCALE, DEFAULT_GAMMA, DEFAULT_SCALE);
+    }
+}
+
+/// Holds image memory allocated by stb and responsible for calling `stbi_image_free` once dropped.
+/// stb allocates memory through the Rust global allocator, so it's accounted as any other
+/// Rust allocation.
+pub struct Data<T> {
+    data: *mut T,
+    size: usize,
+}
+
+impl<T> Data<T> {
+    fn from_raw(data: *mut T, size: usize) -> Self {
         Data { data, size }
     }
 
     /// Returns image memory as a slice
     pub fn as_slice(&self) -> &[T] {
+        // stb leaves optional outputs, such as GIF delays, null
+        if self.data.is_null() {
+            return &[];
+        }
+
         let size = self.size();
         unsafe { slice::from_raw_parts(self.data, size) }
     }
 
-    /// Returns the number of elements (which is width x height x desired_channels)
+    /// Returns the number of elements
     pub fn size(&self) -> usize {
         self.size
     }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        if self.data.is_null() {
+            return &mut [];
+        }
+
+        let size = self.size();
+        unsafe { slice::from_raw_parts_mut(self.data, size) }
+    }
 }
 
+// Memory is owned exclusively and can be freed from any thread
+unsafe impl<T: Send> Send for Data<T> {}
+unsafe impl<T: Sync> Sync for Data<T> {}
+
 impl<T: Clone> Data<T> {
-    /// Consumes this object into Rust owned vector
+    /// Consumes this object into Rust owned vector.
+    /// Memory decoded by loaders is handed over without copying, memory stb allocated
+    /// elsewhere (e.g. converted in place into another type) is copied.
     pub fn into_vec(self) -> Vec<T> {
-        self.as_slice().to_vec()
+        let (data, size) = (self.data, self.size);
+        mem::forget(self);
+
+        // SAFETY: `data` holds `size` elements and isn't used once the vector takes it
+        match unsafe { alloc::take_vec(data, size) } {
+            Ok(vec) => vec,
+            Err(data) => Data::from_raw(data, size).as_slice().to_vec(),
+        }
     }
 }
 
@@ -98,18 +564,439 @@ impl<T> Drop for Data<T> {
     }
 }
 
-/// IO wrapper for stb
+/// Decoded image along with its dimensions and channel layout.
+///
+/// Pixels are stored row by row without padding, each pixel holds `channels` elements.
+pub struct Image<T> {
+    info: Info,
+    width: usize,
+    height: usize,
+    channels: usize,
+    orientation: Orientation,
+    data: Data<T>,
+}
+
+impl<T> Image<T> {
+    fn new(data: *mut T, desired_channels: Channels, info: Info) -> Self {
+        let channels = if desired_channels == Channels::Default {
+            info.components as usize
+        } else {
+            desired_channels as usize
+        };
+
+        let width = info.width as usize;
+        let height = info.height as usize;
+
+        Image {
+            info,
+            width,
+            height,
+            channels,
+            orientation: Orientation::Normal,
+            data: Data::from_raw(data, width * height * channels),
+        }
+    }
+
+    /// Returns image info as reported by the image file.
+    /// Note that `components` is the number of channels in the file, which might be different
+    /// from `channels` if a specific channel layout was requested, and width and height are
+    /// swapped if EXIF orientation was applied.
+    pub fn info(&self) -> Info {
+        self.info
+    }
+
+    /// Image width in pixels
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Image height in pixels
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Number of channels per pixel in decoded data
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// EXIF orientation of decoded pixels, `Orientation::Normal` if the image has no EXIF
+    /// metadata or the orientation was applied with [`LoadOptions::apply_orientation`]
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Returns image memory as a slice
+    pub fn as_slice(&self) -> &[T] {
+        self.data.as_slice()
+    }
+
+    /// Returns a view of the whole image
+    pub fn view(&self) -> ImageView<'_, T> {
+        ImageView {
+            data: self.as_slice(),
+            width: self.width,
+            height: self.height,
+            channels: self.channels,
+            stride: self.width * self.channels,
+        }
+    }
+
+    /// Returns pixels of the row `y`
+    pub fn row(&self, y: usize) -> Option<&[T]> {
+        self.view().row(y)
+    }
+
+    /// Returns an iterator over image rows, from top to bottom
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.view().rows()
+    }
+
+    /// Returns channels of the pixel at `x`, `y`
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<&[T]> {
+        self.view().get_pixel(x, y)
+    }
+
+    /// Returns a view of the `width` x `height` rectangle with the top left corner at `x`, `y`.
+    /// Returns `None` if the rectangle doesn't fit into the image.
+    pub fn crop(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Option<ImageView<'_, T>> {
+        self.view().crop(x, y, width, height)
+    }
+}
+
+impl<T: Clone> Image<T> {
+    /// Consumes this object into Rust owned vector
+    pub fn into_vec(self) -> Vec<T> {
+        self.data.into_vec()
+    }
+}
+
+impl<T: Copy> Image<T> {
+    /// Sets orientation read from the image file, applies it to pixels if requested by `options`
+    fn set_orientation(&mut self, orientation: Orientation, options: &LoadOptions) {
+        if !options.apply_orientation {
+            self.orientation = orientation;
+            return;
+        }
+
+        let (width, height) = orient(
+            self.data.as_mut_slice(),
+            self.width,
+            self.height,
+            self.channels,
+            orientation,
+            options.flip_vertically,
+        );
+
+        self.width = width;
+        self.height = height;
+        if orientation.swaps_dimensions() {
+            mem::swap(&mut self.info.width, &mut self.info.height);
+        }
+    }
+}
+
+/// Reorders `pixels` of `width` x `height` image stored with `orientation` to be upright,
+/// `flipped` if stb flipped rows after decoding. Returns the new width and height.
+fn orient<T: Copy>(
+    pixels: &mut [T],
+    width: usize,
+    height: usize,
+    channels: usize,
+    orientation: Orientation,
+    flipped: bool,
+) -> (usize, usize) {
+    if orientation == Orientation::Normal {
+        return (width, height);
+    }
+
+    let (new_width, new_height) = if orientation.swaps_dimensions() {
+        (height, width)
+    } else {
+        (width, height)
+    };
+
+    // Pixels are flipped by stb after decoding, orientation applies to the image as stored
+    let source = pixels.to_vec();
+
+    for y in 0..new_height {
+        let upright_y = if flipped { new_height - 1 - y } else { y };
+        for x in 0..new_width {
+            let (src_x, src_y) = orientation.source(x, upright_y, width, height);
+            let src_y = if flipped { height - 1 - src_y } else { src_y };
+
+            let src = (src_y * width + src_x) * channels;
+            let dst = (y * new_width + x) * channels;
+            pixels[dst..dst + channels].copy_from_slice(&source[src..src + channels]);
+        }
+    }
+
+    (new_width, new_height)
+}
+
+impl<T> ops::Deref for Image<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+/// Borrowed rectangular region of an image.
+///
+/// Rows are `stride` elements apart, so a view might reference a part of a larger image.
+#[derive(Debug)]
+pub struct ImageView<'a, T> {
+    data: &'a [T],
+    width: usize,
+    height: usize,
+    channels: usize,
+    stride: usize,
+}
+
+impl<'a, T> Clone for ImageView<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for ImageView<'a, T> {}
+
+impl<'a, T> ImageView<'a, T> {
+    /// Creates a view over `data` holding `height` rows of `width` pixels, rows are `stride`
+    /// elements apart. Returns `None` if `data` is too small.
+    pub fn new(
+        data: &'a [T],
+        width: usize,
+        height: usize,
+        channels: usize,
+        stride: usize,
+    ) -> Option<Self> {
+        let row_size = width * channels;
+        if stride < row_size {
+            return None;
+        }
+
+        let size = if height == 0 {
+            0
+        } else {
+            (height - 1) * stride + row_size
+        };
+
+        if data.len() < size {
+            return None;
+        }
+
+        Some(ImageView {
+            data: &data[..size],
+            width,
+            height,
+            channels,
+            stride,
+        })
+    }
+
+    /// View width in pixels
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// View height in pixels
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Number of channels per pixel
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Distance between the beginnings of two consecutive rows in elements
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Distance between the beginnings of two consecutive rows in bytes
+    pub fn stride_in_bytes(&self) -> usize {
+        self.stride * mem::size_of::<T>()
+    }
+
+    /// Returns underlying memory, starting at the first pixel of the view
+    pub fn as_slice(&self) -> &'a [T] {
+        self.data
+    }
+
+    /// Returns pixels of the row `y`
+    pub fn row(&self, y: usize) -> Option<&'a [T]> {
+        if y >= self.height {
+            return None;
+        }
+
+        let start = y * self.stride;
+        Some(&self.data[start..start + self.width * self.channels])
+    }
+
+    /// Returns an iterator over view rows, from top to bottom
+    pub fn rows(&self) -> impl Iterator<Item = &'a [T]> {
+        let view = *self;
+        (0..self.height).filter_map(move |y| view.row(y))
+    }
+
+    /// Returns channels of the pixel at `x`, `y`
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<&'a [T]> {
+        if x >= self.width {
+            return None;
+        }
+
+        let start = x * self.channels;
+        self.row(y).map(|row| &row[start..start + self.channels])
+    }
+
+    /// Returns a view of the `width` x `height` rectangle with the top left corner at `x`, `y`.
+    /// Returns `None` if the rectangle doesn't fit into this view.
+    pub fn crop(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Option<ImageView<'a, T>> {
+        // Corners past `usize::MAX` don't fit either
+        let right = x.checked_add(width).filter(|right| *right <= self.width);
+        let bottom = y
+            .checked_add(height)
+            .filter(|bottom| *bottom <= self.height);
+        if right.is_none() || bottom.is_none() {
+            return None;
+        }
+
+        let start = y * self.stride + x * self.channels;
+        ImageView::new(
+            &self.data[start.min(self.data.len())..],
+            width,
+            height,
+            self.channels,
+            self.stride,
+        )
+    }
+}
+
+impl<'a, T: Clone> ImageView<'a, T> {
+    /// Copies view pixels into a tightly packed vector
+    pub fn to_vec(&self) -> Vec<T> {
+        let mut vec = Vec::with_capacity(self.width * self.height * self.channels);
+        for row in self.rows() {
+            vec.extend_from_slice(row);
+        }
+        vec
+    }
+}
+
+/// Animated image with all its frames composited and decoded at once.
+#[cfg(not(feature = "stbi_no_gif"))]
+pub struct AnimatedImage {
+    info: Info,
+    frame_count: usize,
+    frames: Data<u8>,
+    delays: Data<i32>,
+}
+
+#[cfg(not(feature = "stbi_no_gif"))]
+impl AnimatedImage {
+    /// Returns frame dimensions, `components` is the number of channels of each decoded frame
+    pub fn info(&self) -> Info {
+        self.info
+    }
+
+    /// Returns the number of frames
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Returns the number of elements in a single frame (which is width x height x components),
+    /// or zero if it doesn't fit `usize`.
+    pub fn frame_size(&self) -> usize {
+        self.checked_frame_size().unwrap_or(0)
+    }
+
+    fn checked_frame_size(&self) -> Option<usize> {
+        (self.info.width as usize)
+            .checked_mul(self.info.height as usize)
+            .and_then(|size| size.checked_mul(self.info.components as usize))
+    }
+
+    /// Returns frame pixels by index
+    pub fn frame(&self, index: usize) -> Option<&[u8]> {
+        if index >= self.frame_count {
+            return None;
+        }
+
+        let size = self.frame_size();
+        Some(&self.frames.as_slice()[index * size..(index + 1) * size])
+    }
+
+    /// Returns an iterator over all frames
+    pub fn frames(&self) -> impl Iterator<Item = &[u8]> {
+        // `chunks_exact` doesn't accept zero size, there is nothing to iterate then
+        let size = self.frame_size();
+        let frames = if size == 0 {
+            &[]
+        } else {
+            self.frames.as_slice()
+        };
+
+        frames.chunks_exact(size.max(1)).take(self.frame_count)
+    }
+
+    /// Returns per frame delays in milliseconds
+    pub fn delays(&self) -> &[i32] {
+        self.delays.as_slice()
+    }
+}
+
+/// Number of recently read bytes kept around to serve negative skips
+const LOOKBACK: usize = 4096;
+
+/// IO wrapper for stb.
+///
+/// stb's skip callback may 'unget' recently read bytes, which is emulated with a lookback
+/// buffer, so the wrapped reader doesn't have to implement `io::Seek`.
+///
+/// Panics can't unwind through stb's C frames, so callbacks catch reader panics and report an
+/// IO error to stb instead. The panic is resumed by `resume_panic` once stb returns.
 struct Wrapper<'a, R> {
     reader: &'a mut R,
-    err: bool,
+    /// Recently read bytes, `buffer[pos..]` are ungot bytes to be returned by next reads
+    buffer: Vec<u8>,
+    pos: usize,
+    /// Keep all read bytes, so the stream can be rewound to the beginning
+    keep_all: bool,
+    eof: bool,
+    err: Option<io::Error>,
+    panic: Option<Box<dyn Any + Send>>,
 }
 
 impl<'a, R> Wrapper<'a, R>
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
 {
     fn new(reader: &'a mut R) -> (Wrapper<'a, R>, sys::stbi_io_callbacks) {
-        let reader = Wrapper { reader, err: false };
+        let reader = Wrapper {
+            reader,
+            buffer: Vec::new(),
+            pos: 0,
+            keep_all: false,
+            eof: false,
+            err: None,
+            panic: None,
+        };
+
         let callbacks = sys::stbi_io_callbacks {
             read: Some(Self::io_read),
             skip: Some(Self::io_skip),
@@ -123,40 +1010,87 @@ where
         unsafe { &mut *(user as *mut Wrapper<R>) }
     }
 
-    fn read(&mut self, data: *mut raw::c_char, size: raw::c_int) -> raw::c_int {
-        if self.err {
+    fn read(&mut self, dest: &mut [u8]) -> usize {
+        if self.err.is_some() {
             return 0;
         }
 
-        let dest = unsafe {
-            let data = data as *mut u8;
-            slice::from_raw_parts_mut(data, size as _)
+        // Serve previously ungot bytes first
+        let pending = self.buffer.len() - self.pos;
+        if pending > 0 {
+            let n = pending.min(dest.len());
+            dest[..n].copy_from_slice(&self.buffer[self.pos..self.pos + n]);
+            self.pos += n;
+            return n;
+        }
+
+        let n = loop {
+            match self.reader.read(dest) {
+                Ok(n) => break n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.err = Some(err);
+                    return 0;
+                }
+            }
         };
 
-        if let Ok(n) = self.reader.read(dest) {
-            return n as _;
+        if n == 0 && !dest.is_empty() {
+            self.eof = true;
         }
 
-        self.err = true;
-        0
+        self.remember(&dest[..n]);
+        n
+    }
+
+    /// Appends read bytes to the lookback buffer, keeps at most `LOOKBACK` last bytes
+    fn remember(&mut self, data: &[u8]) {
+        if self.keep_all {
+            self.buffer.extend_from_slice(data);
+        } else if data.len() >= LOOKBACK {
+            self.buffer.clear();
+            self.buffer
+                .extend_from_slice(&data[data.len() - LOOKBACK..]);
+        } else {
+            self.buffer.extend_from_slice(data);
+            // Trim occasionally rather than on each read
+            if self.buffer.len() > LOOKBACK * 2 {
+                self.buffer.drain(..self.buffer.len() - LOOKBACK);
+            }
+        }
+
+        self.pos = self.buffer.len();
     }
 
     fn skip(&mut self, n: raw::c_int) {
+        if self.err.is_some() {
+            return;
+        }
+
         match n.cmp(&0) {
             Ordering::Greater => {
-                if self.reader.seek(io::SeekFrom::Current(n as _)).is_err() {
-                    self.err = true
+                let mut remaining = n as usize;
+                let mut scratch = [0_u8; 512];
+
+                while remaining > 0 {
+                    let len = remaining.min(scratch.len());
+                    let read = self.read(&mut scratch[..len]);
+                    if read == 0 {
+                        break;
+                    }
+                    remaining -= read;
                 }
             }
             Ordering::Less => {
-                // stb allows negative seeks while Rust API considers this as an error
-                if self
-                    .reader
-                    .seek(io::SeekFrom::Current(0)) // Find current position
-                    .and_then(|pos| self.reader.seek(io::SeekFrom::Start(pos - n as u64))) // Seek from start
-                    .is_err()
-                {
-                    self.err = true
+                let n = n.unsigned_abs() as usize;
+                if n > self.pos {
+                    self.err = Some(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "can't unget more bytes than were recently read",
+                    ));
+                } else {
+                    self.pos -= n;
+                    self.eof = false;
                 }
             }
             _ => {
@@ -166,13 +1100,92 @@ where
     }
 
     fn eof(&self) -> raw::c_int {
-        if self.err {
+        if self.err.is_some() || (self.eof && self.pos == self.buffer.len()) {
             1
         } else {
             0
         }
     }
 
+    fn user_data(&mut self) -> *mut ffi::c_void {
+        self as *mut _ as *mut ffi::c_void
+    }
+
+    /// Runs `f` from the beginning of the stream and rewinds back to the beginning once done.
+    /// Must be called before anything else is read from the stream.
+    fn peek<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.keep_all = true;
+        let result = f(self);
+        self.keep_all = false;
+
+        self.pos = 0;
+        self.eof = false;
+
+        result
+    }
+
+    fn info(&mut self, callbacks: &sys::stbi_io_callbacks) -> Result<Info> {
+        let mut info = Info::default();
+
+        let ret = unsafe {
+            sys::stbi_info_from_callbacks(
+                callbacks,
+                self.user_data(),
+                &mut info.width,
+                &mut info.height,
+                &mut info.components,
+            )
+        };
+        self.resume_panic();
+
+        if ret == 0 {
+            Err(self.take_error())
+        } else {
+            Ok(info)
+        }
+    }
+
+    /// Reads image info and rewinds the stream back to the beginning
+    fn probe(&mut self, callbacks: &sys::stbi_io_callbacks) -> Result<Info> {
+        self.peek(|reader| reader.info(callbacks))
+    }
+
+    /// Reads EXIF orientation of JPEG images and rewinds the stream back to the beginning.
+    /// Parsing stops after the first two bytes unless they are the JPEG SOI marker.
+    fn orientation(&mut self) -> Orientation {
+        self.peek(exif::read_orientation).unwrap_or_default()
+    }
+
+    /// Resumes the reader panic caught by callbacks, must be called once stb returns
+    fn resume_panic(&mut self) {
+        if let Some(payload) = self.panic.take() {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Returns the reader error if any, otherwise the reason reported by stb
+    fn take_error(&mut self) -> Error {
+        match self.err.take() {
+            Some(err) => Error::Io(err),
+            None => Error::from_failure_reason(),
+        }
+    }
+
+    /// Runs callback `f` with the wrapper behind `user`, returns `fallback` if it panics.
+    /// Once a callback panicked the stream is treated as failed, so stb stops reading.
+    fn catch<T>(user: *mut raw::c_void, fallback: T, f: impl FnOnce(&mut Self) -> T) -> T {
+        let wrapper = Wrapper::<R>::from_user_data(user);
+
+        match panic::catch_unwind(AssertUnwindSafe(|| f(&mut *wrapper))) {
+            Ok(result) => result,
+            Err(payload) => {
+                wrapper.err = Some(io::Error::other("reader panicked"));
+                wrapper.panic = Some(payload);
+                fallback
+            }
+        }
+    }
+
     /// Fill `data` with `size` bytes.
     /// Return number of bytes actually read
     extern "C" fn io_read(
@@ -180,299 +1193,781 @@ where
         data: *mut raw::c_char,
         size: raw::c_int,
     ) -> raw::c_int {
-        Wrapper::<R>::from_user_data(user).read(data, size)
+        let dest = unsafe { slice::from_raw_parts_mut(data as *mut u8, size as _) };
+        Self::catch(user, 0, |wrapper| wrapper.read(dest) as _)
     }
 
     /// Skip the next `n` bytes, or 'unget' the last `-n` bytes if negative
     extern "C" fn io_skip(user: *mut raw::c_void, n: raw::c_int) {
-        Wrapper::<R>::from_user_data(user).skip(n);
+        Self::catch(user, (), |wrapper| wrapper.skip(n));
     }
 
     /// Returns nonzero if we are at end of file/data
     extern "C" fn io_eof(user: *mut raw::c_void) -> raw::c_int {
-        Wrapper::<R>::from_user_data(user).eof()
+        Self::catch(user, 1, |wrapper| wrapper.eof())
     }
 }
 
-/// By default we convert iphone-formatted PNGs back to RGB, even though they are internally
-/// encoded differently. You can disable this conversion by calling
-/// `stbi_convert_iphone_png_to_rgb(false)`, in which case you will always just get the
-/// native iphone "format" through (which is BGR stored in RGB).
-pub fn stbi_convert_iphone_png_to_rgb(true_if_should_convert: bool) {
-    unsafe { sys::stbi_convert_iphone_png_to_rgb(if true_if_should_convert { 1 } else { 0 }) }
+/// Lets Rust parsers read the stream, reader errors are kept to be reported by the loader
+impl<'a, R: io::Read> io::Read for Wrapper<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(Wrapper::read(self, buf))
+    }
 }
 
-/// Call `stbi_set_unpremultiply_on_load(true)` to force a divide per pixel to remove any
-/// premultiplied alpha *only* if the image file explicitly says there's premultiplied
-/// data (currently only happens in iPhone images, and only if iPhone convert-to-rgb processing is on).
-pub fn stbi_set_unpremultiply_on_load(true_if_should_unpremultiply: bool) {
-    unsafe { sys::stbi_set_unpremultiply_on_load(if true_if_should_unpremultiply { 1 } else { 0 }) }
+/// Signature of `stbi_load*_from_memory` functions
+type MemoryLoader<T> = unsafe extern "C" fn(
+    *const sys::stbi_uc,
+    raw::c_int,
+    *mut raw::c_int,
+    *mut raw::c_int,
+    *mut raw::c_int,
+    raw::c_int,
+) -> *mut T;
+
+/// Signature of `stbi_load*_from_callbacks` functions
+type CallbacksLoader<T> = unsafe extern "C" fn(
+    *const sys::stbi_io_callbacks,
+    *mut ffi::c_void,
+    *mut raw::c_int,
+    *mut raw::c_int,
+    *mut raw::c_int,
+    raw::c_int,
+) -> *mut T;
+
+/// Converts HDR images decoded as float into another sample type
+#[cfg(not(feature = "stbi_no_linear"))]
+type FromHdr<T> = fn(Image<f32>, HdrToLdr) -> Image<T>;
+
+/// Length of `buffer` as stb sees it, stb takes buffer sizes as `int`, so bigger buffers
+/// can't be decoded from memory
+fn buffer_len(buffer: &[u8]) -> Result<raw::c_int> {
+    crate::buffer_len(buffer).ok_or(Error::TooLarge)
 }
 
-/// Flip the image vertically, so the first pixel in the output array is the bottom left
-pub fn stbi_set_flip_vertically_on_load(true_if_should_flip: bool) {
-    unsafe { sys::stbi_set_flip_vertically_on_load(if true_if_should_flip { 1 } else { 0 }) }
+/// Length of `buffer` clamped to `int`, which is enough for functions only looking at headers
+fn header_len(buffer: &[u8]) -> raw::c_int {
+    crate::buffer_len(buffer).unwrap_or(raw::c_int::MAX)
+}
+
+fn load_from_memory<T: Sample>(
+    buffer: &[u8],
+    desired_channels: Channels,
+    options: LoadOptions,
+    load: MemoryLoader<T>,
+) -> Result<Image<T>> {
+    #[cfg(not(feature = "stbi_no_linear"))]
+    if let (Some(conversion), Some(from_hdr)) = (options.hdr_to_ldr, T::FROM_HDR) {
+        if stbi_is_hdr_from_memory(buffer) {
+            // Floats are converted in place, so allocate them for the final sample type
+            let image = alloc::vec_scope::<T, _>(|| {
+                load_from_memory(
+                    buffer,
+                    desired_channels,
+                    options,
+                    sys::stbi_loadf_from_memory,
+                )
+            })?;
+            return Ok(from_hdr(image, conversion));
+        }
+    }
+
+    let len = buffer_len(buffer)?;
+    options.limits.check_memory::<T>(buffer, desired_channels)?;
+
+    let mut image = decode_memory(buffer, len, desired_channels, &options, load)?;
+    image.set_orientation(jpeg_orientation(buffer).unwrap_or_default(), &options);
+    Ok(image)
+}
+
+/// Decodes `buffer` of `len` bytes with `load`, limits are expected to be checked by callers
+fn decode_memory<T>(
+    buffer: &[u8],
+    len: raw::c_int,
+    desired_channels: Channels,
+    options: &LoadOptions,
+    load: MemoryLoader<T>,
+) -> Result<Image<T>> {
+    let mut info = Info::default();
+
+    let data = alloc::vec_scope::<T, _>(|| {
+        options.scope(|| unsafe {
+            load(
+                buffer.as_ptr(),
+                len,
+                &mut info.width,
+                &mut info.height,
+                &mut info.components,
+                desired_channels as i32,
+            )
+        })
+    });
+
+    if data.is_null() {
+        return Err(Error::from_failure_reason());
+    }
+
+    Ok(Image::new(data, desired_channels, info))
+}
+
+fn load_from_wrapper<T, R>(
+    reader: &mut Wrapper<R>,
+    callbacks: &sys::stbi_io_callbacks,
+    desired_channels: Channels,
+    options: LoadOptions,
+    load: CallbacksLoader<T>,
+) -> Result<Image<T>>
+where
+    T: Sample,
+    R: io::Read,
+{
+    #[cfg(not(feature = "stbi_no_linear"))]
+    if let (Some(conversion), Some(from_hdr)) = (options.hdr_to_ldr, T::FROM_HDR) {
+        let is_hdr = reader.peek(|reader| unsafe {
+            sys::stbi_is_hdr_from_callbacks(callbacks, reader.user_data()) != 0
+        });
+        reader.resume_panic();
+
+        if is_hdr {
+            let image = alloc::vec_scope::<T, _>(|| {
+                load_from_wrapper(
+                    reader,
+                    callbacks,
+                    desired_channels,
+                    options,
+                    sys::stbi_loadf_from_callbacks,
+                )
+            })?;
+            return Ok(from_hdr(image, conversion));
+        }
+    }
+
+    options
+        .limits
+        .check_reader::<T, _>(reader, callbacks, desired_channels)?;
+
+    // Only segments up to EXIF are buffered, so orientation is reported even if not applied
+    let orientation = reader.orientation();
+
+    let mut info = Info::default();
+
+    let data = alloc::vec_scope::<T, _>(|| {
+        options.scope(|| unsafe {
+            load(
+                callbacks,
+                reader.user_data(),
+                &mut info.width,
+                &mut info.height,
+                &mut info.components,
+                desired_channels as i32,
+            )
+        })
+    });
+
+    if data.is_null() {
+        reader.resume_panic();
+        return Err(reader.take_error());
+    }
+
+    // Decoded data is freed while unwinding
+    let mut image = Image::new(data, desired_channels, info);
+    reader.resume_panic();
+
+    image.set_orientation(orientation, &options);
+    Ok(image)
 }
 
 /// Get image dimensions & components from a slice without fully decoding
-pub fn stbi_info_from_memory(buffer: &[u8]) -> Option<Info> {
+pub fn stbi_info_from_memory(buffer: &[u8]) -> Result<Info> {
     let mut info = Info::default();
     let ret = unsafe {
         sys::stbi_info_from_memory(
             buffer.as_ptr(),
-            buffer.len() as i32,
+            header_len(buffer),
             &mut info.width,
             &mut info.height,
             &mut info.components,
         )
     };
     if ret == 0 {
-        None
+        Err(Error::from_failure_reason())
     } else {
-        Some(info)
+        Ok(info)
     }
 }
 
 /// Get image dimensions & components from reader without fully decoding
-pub fn stbi_info_from_reader<R>(reader: &mut R) -> Option<Info>
+pub fn stbi_info_from_reader<R>(reader: &mut R) -> Result<Info>
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
 {
     let (mut reader, callbacks) = Wrapper::new(reader);
-    let mut info = Info::default();
-
-    let ret = unsafe {
-        sys::stbi_info_from_callbacks(
-            &callbacks,
-            &mut reader as *mut _ as *mut ffi::c_void,
-            &mut info.width,
-            &mut info.height,
-            &mut info.components,
-        )
-    };
-
-    if ret == 0 {
-        None
-    } else {
-        Some(info)
-    }
+    reader.info(&callbacks)
 }
 
 pub fn stbi_is_16_bit_from_memory(buffer: &[u8]) -> bool {
-    let ret = unsafe { sys::stbi_is_16_bit_from_memory(buffer.as_ptr(), buffer.len() as i32) };
+    let ret = unsafe { sys::stbi_is_16_bit_from_memory(buffer.as_ptr(), header_len(buffer)) };
     ret == 1
 }
 
 pub fn stbi_is_16_bit_from_reader<R>(reader: &mut R) -> bool
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
 {
     let (mut reader, callbacks) = Wrapper::new(reader);
-    let ret = unsafe {
-        sys::stbi_is_16_bit_from_callbacks(&callbacks, &mut reader as *mut _ as *mut ffi::c_void)
-    };
+    let ret = unsafe { sys::stbi_is_16_bit_from_callbacks(&callbacks, reader.user_data()) };
+    reader.resume_panic();
     ret == 1
 }
 
+/// Returns `true` if the image is HDR (Radiance rgbE) and holds floating point data.
+/// Always `false` if HDR support is disabled with `stbi_no_hdr` feature.
+pub fn stbi_is_hdr_from_memory(buffer: &[u8]) -> bool {
+    let ret = unsafe { sys::stbi_is_hdr_from_memory(buffer.as_ptr(), header_len(buffer)) };
+    ret != 0
+}
+
+/// Returns `true` if the image is HDR (Radiance rgbE) and holds floating point data.
+/// Always `false` if HDR support is disabled with `stbi_no_hdr` feature.
+pub fn stbi_is_hdr_from_reader<R>(reader: &mut R) -> bool
+where
+    R: io::Read,
+{
+    let (mut reader, callbacks) = Wrapper::new(reader);
+    let ret = unsafe { sys::stbi_is_hdr_from_callbacks(&callbacks, reader.user_data()) };
+    reader.resume_panic();
+    ret != 0
+}
+
+/// 8-bits-per-channel interface, load image from memory
 pub fn stbi_load_from_memory(
     buffer: &[u8],
     desired_channels: Channels,
-) -> Option<(Info, Data<u8>)> {
+    options: LoadOptions,
+) -> Result<Image<u8>> {
+    load_from_memory(
+        buffer,
+        desired_channels,
+        options,
+        sys::stbi_load_from_memory,
+    )
+}
+
+/// 8-bits-per-channel interface, load image from reader
+pub fn stbi_load_from_reader<R>(
+    reader: &mut R,
+    desired_channels: Channels,
+    options: LoadOptions,
+) -> Result<Image<u8>>
+where
+    R: io::Read,
+{
+    let (mut reader, callbacks) = Wrapper::new(reader);
+    load_from_wrapper(
+        &mut reader,
+        &callbacks,
+        desired_channels,
+        options,
+        sys::stbi_load_from_callbacks,
+    )
+}
+
+/// 16-bits-per-channel interface, load image from memory
+pub fn stbi_load_16_from_memory(
+    buffer: &[u8],
+    desired_channels: Channels,
+    options: LoadOptions,
+) -> Result<Image<u16>> {
+    load_from_memory(
+        buffer,
+        desired_channels,
+        options,
+        sys::stbi_load_16_from_memory,
+    )
+}
+
+/// 16-bits-per-channel interface, load image from reader
+pub fn stbi_load_16_from_reader<R>(
+    reader: &mut R,
+    desired_channels: Channels,
+    options: LoadOptions,
+) -> Result<Image<u16>>
+where
+    R: io::Read,
+{
+    let (mut reader, callbacks) = Wrapper::new(reader);
+    load_from_wrapper(
+        &mut reader,
+        &callbacks,
+        desired_channels,
+        options,
+        sys::stbi_load_16_from_callbacks,
+    )
+}
+
+/// Load all frames of an animated GIF from memory.
+/// Frames are composited, so each of them contains the full image rather than a delta
+/// from the previous one.
+#[cfg(not(feature = "stbi_no_gif"))]
+pub fn stbi_load_gif_from_memory(
+    buffer: &[u8],
+    desired_channels: Channels,
+    options: LoadOptions,
+) -> Result<AnimatedImage> {
+    options.limits.check_gif(buffer)?;
+
+    let len = buffer_len(buffer)?;
     let mut info = Info::default();
+    let mut delays = std::ptr::null_mut();
+    let mut frame_count = 0;
+
+    let data = alloc::vec_scope::<u8, _>(|| {
+        options.scope(|| unsafe {
+            sys::stbi_load_gif_from_memory(
+                buffer.as_ptr(),
+                len,
+                &mut delays,
+                &mut info.width,
+                &mut info.height,
+                &mut frame_count,
+                &mut info.components,
+                desired_channels as i32,
+            )
+        })
+    });
 
-    let data = unsafe {
-        sys::stbi_load_from_memory(
-            buffer.as_ptr(),
-            buffer.len() as i32,
-            &mut info.width,
-            &mut info.height,
-            &mut info.components,
-            desired_channels as i32,
-        )
+    if data.is_null() {
+        return Err(Error::from_failure_reason());
+    }
+
+    if desired_channels != Channels::Default {
+        info.components = desired_channels as i32;
+    }
+
+    let frame_count = frame_count as usize;
+    let mut image = AnimatedImage {
+        info,
+        frame_count,
+        frames: Data::from_raw(data, 0),
+        delays: Data::from_raw(delays, if delays.is_null() { 0 } else { frame_count }),
+    };
+
+    // Data is freed on error, as it's already owned by the image
+    image.frames.size = image
+        .checked_frame_size()
+        .and_then(|size| size.checked_mul(frame_count))
+        .ok_or(Error::TooLarge)?;
+
+    Ok(image)
+}
+
+/// Float interface, load image from memory
+#[cfg(not(feature = "stbi_no_linear"))]
+pub fn stbi_loadf_from_memory(
+    buffer: &[u8],
+    desired_channels: Channels,
+    options: LoadOptions,
+) -> Result<Image<f32>> {
+    load_from_memory(
+        buffer,
+        desired_channels,
+        options,
+        sys::stbi_loadf_from_memory,
+    )
+}
+
+/// Float interface, load image from reader
+#[cfg(not(feature = "stbi_no_linear"))]
+pub fn stbi_loadf_from_reader<R>(
+    reader: &mut R,
+    desired_channels: Channels,
+    options: LoadOptions,
+) -> Result<Image<f32>>
+where
+    R: io::Read,
+{
+    let (mut reader, callbacks) = Wrapper::new(reader);
+    load_from_wrapper(
+        &mut reader,
+        &callbacks,
+        desired_channels,
+        options,
+        sys::stbi_loadf_from_callbacks,
+    )
+}
+
+/// Sample types images can be decoded into: `u8`, `u16` and `f32` (unless `stbi_no_linear`)
+pub trait Sample: private::Sealed + Copy {}
+
+mod private {
+    use super::{CallbacksLoader, MemoryLoader};
+    #[cfg(not(feature = "stbi_no_linear"))]
+    use super::{FromHdr, Image};
+    use stb_sys as sys;
+
+    pub trait Sealed: Sized {
+        const MEMORY_LOADER: MemoryLoader<Self>;
+        const CALLBACKS_LOADER: CallbacksLoader<Self>;
+        /// Converts HDR images decoded as float if `LoadOptions::hdr_to_ldr` is set,
+        /// `None` leaves the conversion to stb
+        #[cfg(not(feature = "stbi_no_linear"))]
+        const FROM_HDR: Option<FromHdr<Self>> = None;
+    }
+
+    impl Sealed for u8 {
+        const MEMORY_LOADER: MemoryLoader<u8> = sys::stbi_load_from_memory;
+        const CALLBACKS_LOADER: CallbacksLoader<u8> = sys::stbi_load_from_callbacks;
+        #[cfg(not(feature = "stbi_no_linear"))]
+        const FROM_HDR: Option<FromHdr<u8>> = Some(Image::into_ldr);
+    }
+
+    impl Sealed for u16 {
+        const MEMORY_LOADER: MemoryLoader<u16> = sys::stbi_load_16_from_memory;
+        const CALLBACKS_LOADER: CallbacksLoader<u16> = sys::stbi_load_16_from_callbacks;
+    }
+
+    #[cfg(not(feature = "stbi_no_linear"))]
+    impl Sealed for f32 {
+        const MEMORY_LOADER: MemoryLoader<f32> = sys::stbi_loadf_from_memory;
+        const CALLBACKS_LOADER: CallbacksLoader<f32> = sys::stbi_loadf_from_callbacks;
+    }
+}
+
+impl Sample for u8 {}
+
+impl Sample for u16 {}
+
+#[cfg(not(feature = "stbi_no_linear"))]
+impl Sample for f32 {}
+
+/// Load image from memory into `dest`, which must be able to hold width x height x channels
+/// elements. Image size is validated with `stbi_info_from_memory` before decoding.
+///
+/// stb decodes straight into `dest` when its output allocation fits into it, otherwise pixels
+/// are copied from stb memory. Some decoders ask for spare bytes (stb's JPEG decoder allocates
+/// one more byte), a slightly larger `dest` lets them decode in place too. Use [`Decoder`] to
+/// reuse memory stb allocates while decoding many images.
+pub fn load_into<T: Sample>(
+    buffer: &[u8],
+    dest: &mut [T],
+    desired_channels: Channels,
+    options: LoadOptions,
+) -> Result<Info> {
+    let len = buffer_len(buffer)?;
+    let info = stbi_info_from_memory(buffer)?;
+    options.limits.check::<T>(info, desired_channels)?;
+
+    let channels = if desired_channels == Channels::Default {
+        info.components
+    } else {
+        desired_channels as i32
     };
 
-    if data.is_null() {
-        None
+    let required = (info.width as usize)
+        .checked_mul(info.height as usize)
+        .and_then(|pixels| pixels.checked_mul(channels as usize))
+        .ok_or(Error::TooLarge)?;
+    if dest.len() < required {
+        return Err(Error::BufferTooSmall(required));
+    }
+
+    #[cfg(not(feature = "stbi_no_linear"))]
+    if let (Some(conversion), Some(from_hdr)) = (options.hdr_to_ldr, T::FROM_HDR) {
+        if stbi_is_hdr_from_memory(buffer) {
+            // Floats are converted afterwards, so they can't be decoded in place
+            options.limits.check::<f32>(info, desired_channels)?;
+            let image = decode_memory(
+                buffer,
+                len,
+                desired_channels,
+                &options,
+                sys::stbi_loadf_from_memory,
+            )?;
+            let image = from_hdr(image, conversion);
+            copy_into(&image, dest)?;
+            return Ok(image.info());
+        }
+    }
+
+    let dest_ptr = dest.as_mut_ptr();
+    let min_size = required * mem::size_of::<T>();
+    // SAFETY: samples are plain numbers, so any bytes stb writes are valid, memory decoded in
+    // place is taken back below
+    let image = unsafe {
+        alloc::lend(dest, min_size, || {
+            decode_memory(buffer, len, desired_channels, &options, T::MEMORY_LOADER)
+        })
+    }?;
+
+    let (info, channels) = (image.info, image.channels);
+    if image.data.data == dest_ptr {
+        sys::alloc::take(dest_ptr as *mut ffi::c_void);
+        mem::forget(image);
     } else {
-        Some((info, Data::new(data, desired_channels, info)))
+        copy_into(&image, dest)?;
+    }
+
+    if !options.apply_orientation {
+        return Ok(info);
     }
+
+    let orientation = jpeg_orientation(buffer).unwrap_or_default();
+    let (width, height) = (info.width as usize, info.height as usize);
+    let (width, height) = orient(
+        &mut dest[..width * height * channels],
+        width,
+        height,
+        channels,
+        orientation,
+        options.flip_vertically,
+    );
+
+    Ok(Info {
+        width: width as i32,
+        height: height as i32,
+        ..info
+    })
 }
 
-/// 8-bits-per-channel interface, load image from reader
-pub fn stbi_load_from_reader<R>(
-    reader: &mut R,
-    desired_channels: Channels,
-) -> Option<(Info, Data<u8>)>
-where
-    R: io::Read + io::Seek,
-{
-    let (mut reader, callbacks) = Wrapper::new(reader);
-    let mut info = Info::default();
+/// Copies pixels of `image` into `dest`
+fn copy_into<T: Copy>(image: &Image<T>, dest: &mut [T]) -> Result<()> {
+    dest.get_mut(..image.len())
+        .ok_or(Error::BufferTooSmall(image.len()))?
+        .copy_from_slice(image);
+    Ok(())
+}
 
-    let data = unsafe {
-        sys::stbi_load_from_callbacks(
-            &callbacks,
-            &mut reader as *mut _ as *mut ffi::c_void,
-            &mut info.width,
-            &mut info.height,
-            &mut info.components,
-            desired_channels as i32,
-        )
-    };
+/// Reusable decoder for decoding many images in a row.
+///
+/// Memory stb allocates while decoding (including pixels which don't decode in place) is kept
+/// by the decoder and reused by subsequent decodes instead of going back to the allocator.
+pub struct Decoder {
+    options: LoadOptions,
+    pool: alloc::Pool,
+}
 
-    if data.is_null() {
-        None
-    } else {
-        Some((info, Data::new(data, desired_channels, info)))
+impl Decoder {
+    pub fn new(options: LoadOptions) -> Self {
+        Decoder {
+            options,
+            pool: alloc::Pool::new(),
+        }
+    }
+
+    /// Load image from memory into `dest`, see [`load_into`]
+    pub fn load_into<T: Sample>(
+        &mut self,
+        buffer: &[u8],
+        dest: &mut [T],
+        desired_channels: Channels,
+    ) -> Result<Info> {
+        let options = self.options;
+        self.pool
+            .scope(|| load_into(buffer, dest, desired_channels, options))
+    }
+
+    /// Releases memory kept for reuse
+    pub fn shrink(&mut self) {
+        self.pool.clear();
     }
 }
 
-/// 16-bits-per-channel interface, load image from memory
-pub fn stbi_load_16_from_memory(
-    buffer: &[u8],
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder::new(LoadOptions::default())
+    }
+}
+
+/// Load image from file at `path`.
+/// The file is opened with `std::fs` and streamed to stb through a buffered reader, so any
+/// path representable by `Path` works and IO errors are reported as `Error::Io`.
+pub fn load_path<T: Sample>(
+    path: impl AsRef<Path>,
     desired_channels: Channels,
-) -> Option<(Info, Data<u16>)> {
-    let mut info = Info::default();
+    options: LoadOptions,
+) -> Result<Image<T>> {
+    let mut file = io::BufReader::new(fs::File::open(path)?);
+
+    let (mut reader, callbacks) = Wrapper::new(&mut file);
+    load_from_wrapper(
+        &mut reader,
+        &callbacks,
+        desired_channels,
+        options,
+        T::CALLBACKS_LOADER,
+    )
+}
 
-    let data = unsafe {
-        sys::stbi_load_16_from_memory(
-            buffer.as_ptr(),
-            buffer.len() as i32,
-            &mut info.width,
-            &mut info.height,
-            &mut info.components,
-            desired_channels as i32,
-        )
-    };
+/// Get image dimensions & components from file at `path` without fully decoding
+pub fn info_path(path: impl AsRef<Path>) -> Result<Info> {
+    let mut file = io::BufReader::new(fs::File::open(path)?);
+    stbi_info_from_reader(&mut file)
+}
 
-    if data.is_null() {
-        None
-    } else {
-        Some((info, Data::new(data, desired_channels, info)))
+/// Load image from file at `path` by mapping it into memory.
+///
+/// The mapping is handed to the memory loaders, so large files are neither copied into a `Vec`
+/// nor streamed through many small reads. Files larger than `i32::MAX` bytes (more than stb's
+/// memory loaders accept) are decoded from the mapping through the reader interface instead.
+///
+/// The file must not be modified while it's being decoded.
+#[cfg(feature = "mmap")]
+pub fn load_mmap<T: Sample>(
+    path: impl AsRef<Path>,
+    desired_channels: Channels,
+    options: LoadOptions,
+) -> Result<Image<T>> {
+    let file = fs::File::open(path)?;
+    // SAFETY: the mapping is only read while decoding, callers are required not to modify the
+    // file meanwhile.
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+
+    if buffer_len(&map).is_ok() {
+        return load_from_memory(&map, desired_channels, options, T::MEMORY_LOADER);
     }
+
+    let mut data = &map[..];
+    let (mut reader, callbacks) = Wrapper::new(&mut data);
+    load_from_wrapper(
+        &mut reader,
+        &callbacks,
+        desired_channels,
+        options,
+        T::CALLBACKS_LOADER,
+    )
 }
 
-pub fn stbi_load_16_from_reader<R>(
-    reader: &mut R,
-    desired_channels: Channels,
-) -> Option<(Info, Data<u16>)>
-where
-    R: io::Read + io::Seek,
-{
-    let (mut reader, callbacks) = Wrapper::new(reader);
-    let mut info = Info::default();
+/// Image decoded with its native precision
+pub enum DynamicImage {
+    /// 8-bits-per-channel image
+    U8(Image<u8>),
+    /// 16-bits-per-channel image
+    U16(Image<u16>),
+    /// HDR image
+    #[cfg(not(feature = "stbi_no_linear"))]
+    F32(Image<f32>),
+}
 
-    let data = unsafe {
-        sys::stbi_load_16_from_callbacks(
-            &callbacks,
-            &mut reader as *mut _ as *mut ffi::c_void,
-            &mut info.width,
-            &mut info.height,
-            &mut info.components,
-            desired_channels as i32,
-        )
-    };
+impl DynamicImage {
+    /// Returns image info as reported by the image file
+    pub fn info(&self) -> Info {
+        match self {
+            DynamicImage::U8(image) => image.info(),
+            DynamicImage::U16(image) => image.info(),
+            #[cfg(not(feature = "stbi_no_linear"))]
+            DynamicImage::F32(image) => image.info(),
+        }
+    }
 
-    if data.is_null() {
-        None
-    } else {
-        Some((info, Data::new(data, desired_channels, info)))
+    /// Image width in pixels
+    pub fn width(&self) -> usize {
+        self.info().width as usize
+    }
+
+    /// Image height in pixels
+    pub fn height(&self) -> usize {
+        self.info().height as usize
+    }
+
+    /// Number of channels per pixel in decoded data
+    pub fn channels(&self) -> usize {
+        match self {
+            DynamicImage::U8(image) => image.channels(),
+            DynamicImage::U16(image) => image.channels(),
+            #[cfg(not(feature = "stbi_no_linear"))]
+            DynamicImage::F32(image) => image.channels(),
+        }
     }
 }
 
-#[cfg(not(feature = "stbi_no_linear"))]
-pub fn stbi_loadf_from_memory(
+/// Load image from memory, picking 8-bit, 16-bit or float interface depending on the
+/// image precision, so no precision is lost.
+pub fn load_dynamic(
     buffer: &[u8],
     desired_channels: Channels,
-) -> Option<(Info, Data<f32>)> {
-    let mut info = Info::default();
-
-    let data = unsafe {
-        sys::stbi_loadf_from_memory(
-            buffer.as_ptr(),
-            buffer.len() as i32,
-            &mut info.width,
-            &mut info.height,
-            &mut info.components,
-            desired_channels as i32,
-        )
-    };
+    options: LoadOptions,
+) -> Result<DynamicImage> {
+    #[cfg(not(feature = "stbi_no_linear"))]
+    {
+        if stbi_is_hdr_from_memory(buffer) {
+            return stbi_loadf_from_memory(buffer, desired_channels, options)
+                .map(DynamicImage::F32);
+        }
+    }
 
-    if data.is_null() {
-        None
+    if stbi_is_16_bit_from_memory(buffer) {
+        stbi_load_16_from_memory(buffer, desired_channels, options).map(DynamicImage::U16)
     } else {
-        Some((info, Data::new(data, desired_channels, info)))
+        stbi_load_from_memory(buffer, desired_channels, options).map(DynamicImage::U8)
     }
 }
 
-#[cfg(not(feature = "stbi_no_linear"))]
-pub fn stbi_loadf_from_reader<R>(
+/// Load image from reader, picking 8-bit, 16-bit or float interface depending on the
+/// image precision, so no precision is lost.
+pub fn load_dynamic_from_reader<R>(
     reader: &mut R,
     desired_channels: Channels,
-) -> Option<(Info, Data<f32>)>
+    options: LoadOptions,
+) -> Result<DynamicImage>
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
 {
     let (mut reader, callbacks) = Wrapper::new(reader);
-    let mut info = Info::default();
 
-    let data = unsafe {
-        sys::stbi_loadf_from_callbacks(
+    #[cfg(not(feature = "stbi_no_linear"))]
+    {
+        let is_hdr = reader.peek(|reader| unsafe {
+            sys::stbi_is_hdr_from_callbacks(&callbacks, reader.user_data()) != 0
+        });
+        reader.resume_panic();
+
+        if is_hdr {
+            return load_from_wrapper(
+                &mut reader,
+                &callbacks,
+                desired_channels,
+                options,
+                sys::stbi_loadf_from_callbacks,
+            )
+            .map(DynamicImage::F32);
+        }
+    }
+
+    let is_16_bit = reader.peek(|reader| unsafe {
+        sys::stbi_is_16_bit_from_callbacks(&callbacks, reader.user_data()) != 0
+    });
+    reader.resume_panic();
+
+    if is_16_bit {
+        load_from_wrapper(
+            &mut reader,
             &callbacks,
-            &mut reader as *mut _ as *mut ffi::c_void,
-            &mut info.width,
-            &mut info.height,
-            &mut info.components,
-            desired_channels as i32,
+            desired_channels,
+            options,
+            sys::stbi_load_16_from_callbacks,
         )
-    };
-
-    if data.is_null() {
-        None
+        .map(DynamicImage::U16)
     } else {
-        Some((info, Data::new(data, desired_channels, info)))
+        load_from_wrapper(
+            &mut reader,
+            &callbacks,
+            desired_channels,
+            options,
+            sys::stbi_load_from_callbacks,
+        )
+        .map(DynamicImage::U8)
     }
 }
 
-#[cfg(not(feature = "stbi_no_hdr"))]
-pub fn stbi_hdr_to_ldr_gamma(gamma: f32) {
-    unsafe { sys::stbi_hdr_to_ldr_gamma(gamma) }
-}
-
-#[cfg(not(feature = "stbi_no_hdr"))]
-pub fn stbi_hdr_to_ldr_scale(scale: f32) {
-    unsafe { sys::stbi_hdr_to_ldr_scale(scale) }
-}
-
-#[cfg(not(feature = "stbi_no_linear"))]
-pub fn stbi_ldr_to_hdr_gamma(gamma: f32) {
-    unsafe { sys::stbi_ldr_to_hdr_gamma(gamma) }
-}
-
-#[cfg(not(feature = "stbi_no_linear"))]
-pub fn stbi_ldr_to_hdr_scale(scale: f32) {
-    unsafe { sys::stbi_ldr_to_hdr_scale(scale) }
-}
-
 #[cfg(test)]
 mod tests {
+    use super::test_util::{fixture, fixture_path};
     use super::*;
     use std::fs;
-    use std::path::PathBuf;
-
-    fn fixture_path(file: &str) -> PathBuf {
-        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let mut path = PathBuf::from(root.parent().unwrap());
-
-        path.push("tests/fixtures");
-        path.push(file);
-
-        path
-    }
 
     #[test]
     fn info_from_memory() {
-        let data = fs::read(fixture_path("white.png")).expect("Failed to read test file");
+        let data = fixture("white.png");
 
         let info = stbi_info_from_memory(&data).expect("Failed to get image info from memory");
         assert_eq!(info.width, 20);
@@ -492,9 +1987,10 @@ mod tests {
 
     #[test]
     fn load_8bit_from_memory() {
-        let data = fs::read(fixture_path("white.png")).expect("Failed to read test file");
-        let (info, image) =
-            stbi_load_from_memory(&data, Channels::Grey).expect("Failed to load image");
+        let data = fixture("white.png");
+        let image = stbi_load_from_memory(&data, Channels::Grey, LoadOptions::default())
+            .expect("Failed to load image");
+        let info = image.info();
 
         assert_eq!(info.width, 20);
         assert_eq!(info.height, 30);
@@ -511,8 +2007,9 @@ mod tests {
     #[test]
     fn load_8bit_from_reader() {
         let mut f = fs::File::open(fixture_path("white.png")).expect("Failed to open file reader");
-        let (info, image) = stbi_load_from_reader(&mut f, Channels::Grey)
+        let image = stbi_load_from_reader(&mut f, Channels::Grey, LoadOptions::default())
             .expect("Failed to load image from reader");
+        let info = image.info();
 
         assert_eq!(info.width, 20);
         assert_eq!(info.height, 30);
@@ -526,11 +2023,83 @@ mod tests {
         }
     }
 
+    #[test]
+    fn load_8bit_from_non_seekable_reader() {
+        /// Returns data in small chunks and doesn't implement `io::Seek`
+        struct Chunked<'a>(&'a [u8]);
+
+        impl io::Read for Chunked<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let len = buf.len().min(self.0.len()).min(7);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let data = fixture("white.png");
+
+        let info = stbi_info_from_reader(&mut Chunked(&data)).expect("Failed to get image info");
+        assert_eq!(info.width, 20);
+        assert_eq!(info.height, 30);
+
+        let image =
+            stbi_load_from_reader(&mut Chunked(&data), Channels::Grey, LoadOptions::default())
+                .expect("Failed to load image from non seekable reader");
+        assert_eq!(image.len(), 600);
+        assert!(image.iter().all(|c| *c == u8::MAX));
+    }
+
+    #[test]
+    fn wrapper_skip() {
+        let data = (0..=255).collect::<Vec<u8>>();
+        let mut reader = &data[..];
+        let (mut wrapper, _) = Wrapper::new(&mut reader);
+
+        let mut buf = [0; 4];
+        assert_eq!(wrapper.read(&mut buf), 4);
+        assert_eq!(buf, [0, 1, 2, 3]);
+
+        // Skip forward
+        wrapper.skip(10);
+        assert_eq!(wrapper.read(&mut buf), 4);
+        assert_eq!(buf, [14, 15, 16, 17]);
+
+        // Unget bytes, including some that were skipped over
+        wrapper.skip(-6);
+        assert_eq!(wrapper.read(&mut buf), 4);
+        assert_eq!(buf, [12, 13, 14, 15]);
+
+        // Skip forward through ungot bytes
+        wrapper.skip(-4);
+        wrapper.skip(6);
+        assert_eq!(wrapper.read(&mut buf), 4);
+        assert_eq!(buf, [18, 19, 20, 21]);
+
+        // Skip till the end
+        wrapper.skip(1000);
+        assert_eq!(wrapper.eof(), 1);
+        assert_eq!(wrapper.read(&mut buf), 0);
+
+        // Unget at the end of the stream
+        wrapper.skip(-2);
+        assert_eq!(wrapper.eof(), 0);
+        assert_eq!(wrapper.read(&mut buf), 2);
+        assert_eq!(&buf[..2], &[254, 255]);
+        assert!(wrapper.err.is_none());
+
+        // Can't unget beyond the beginning of the stream
+        wrapper.skip(-1000);
+        assert_eq!(wrapper.eof(), 1);
+        assert!(wrapper.err.is_some());
+    }
+
     #[test]
     fn load_16bit_from_memory() {
-        let data = fs::read(fixture_path("white.png")).expect("Failed to read test file");
-        let (info, image) =
-            stbi_load_16_from_memory(&data, Channels::Default).expect("Failed to load image");
+        let data = fixture("white.png");
+        let image = stbi_load_16_from_memory(&data, Channels::Default, LoadOptions::default())
+            .expect("Failed to load image");
+        let info = image.info();
 
         assert_eq!(info.width, 20);
         assert_eq!(info.height, 30);
@@ -546,9 +2115,10 @@ mod tests {
 
     #[test]
     fn load_16bit_from_memory_remap_channels() {
-        let data = fs::read(fixture_path("white.png")).expect("Failed to read test file");
-        let (info, image) =
-            stbi_load_16_from_memory(&data, Channels::GreyAlpha).expect("Failed to load image");
+        let data = fixture("white.png");
+        let image = stbi_load_16_from_memory(&data, Channels::GreyAlpha, LoadOptions::default())
+            .expect("Failed to load image");
+        let info = image.info();
 
         assert_eq!(info.width, 20);
         assert_eq!(info.height, 30);
@@ -564,15 +2134,655 @@ mod tests {
 
     #[test]
     fn into_vec() {
-        let data = fs::read(fixture_path("white.png")).expect("Failed to read test file");
-        let (_, image) =
-            stbi_load_from_memory(&data, Channels::Grey).expect("Failed to load image");
+        let data = fixture("white.png");
+        let image = stbi_load_from_memory(&data, Channels::Grey, LoadOptions::default())
+            .expect("Failed to load image");
 
+        // Decoded memory is handed over without copying
+        let pixels = image.as_ptr();
         let v = image.into_vec();
+        assert_eq!(v.as_ptr(), pixels);
 
         assert_eq!(v.len(), 600);
         for c in v {
             assert_eq!(c, 255);
         }
     }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_gif"))]
+    fn load_gif_from_memory() {
+        let data = fixture("animated.gif");
+        let image = stbi_load_gif_from_memory(&data, Channels::Default, LoadOptions::default())
+            .expect("Failed to load GIF");
+
+        let info = image.info();
+        assert_eq!(info.width, 2);
+        assert_eq!(info.height, 2);
+        assert_eq!(info.components, 4);
+
+        assert_eq!(image.frame_count(), 2);
+        assert_eq!(image.frames().count(), 2);
+        assert_eq!(image.delays(), &[100, 200]);
+
+        let first = image.frame(0).expect("Missing first frame");
+        assert_eq!(first.len(), 16);
+        assert_eq!(&first[..4], &[255, 0, 0, 255]);
+        assert_eq!(&first[8..12], &[0, 0, 255, 255]);
+
+        let blue = image.frame(1).expect("Missing second frame");
+        assert_eq!(&blue[..4], &[0, 0, 255, 255]);
+
+        assert!(image.frame(2).is_none());
+    }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_gif"))]
+    fn animated_frames_bounds() {
+        let empty = AnimatedImage {
+            info: Info {
+                width: 0,
+                height: 2,
+                components: 4,
+            },
+            frame_count: 3,
+            frames: Data::from_raw(std::ptr::null_mut(), 0),
+            delays: Data::from_raw(std::ptr::null_mut(), 0),
+        };
+        assert_eq!(empty.frame_size(), 0);
+        assert_eq!(empty.frames().count(), 0);
+        assert_eq!(empty.frame(1), Some(&[][..]));
+        assert!(empty.delays().is_empty());
+
+        // Frame size overflows `i32`, but not `usize`
+        let huge = AnimatedImage {
+            info: Info {
+                width: i32::MAX,
+                height: i32::MAX,
+                components: 4,
+            },
+            frame_count: 0,
+            frames: Data::from_raw(std::ptr::null_mut(), 0),
+            delays: Data::from_raw(std::ptr::null_mut(), 0),
+        };
+        assert_eq!(huge.frames().count(), 0);
+        assert!(huge.frame(0).is_none());
+
+        // Frame size overflows `usize`, which loaders report as too large
+        let overflow = AnimatedImage {
+            info: Info {
+                width: i32::MAX,
+                height: i32::MAX,
+                components: i32::MAX,
+            },
+            frame_count: 1,
+            frames: Data::from_raw(std::ptr::null_mut(), 0),
+            delays: Data::from_raw(std::ptr::null_mut(), 0),
+        };
+        assert_eq!(overflow.checked_frame_size(), None);
+        assert_eq!(overflow.frame_size(), 0);
+        assert_eq!(overflow.frames().count(), 0);
+    }
+
+    /// Builds an animated GIF of `frames` black 1 x 1 frames
+    #[cfg(not(feature = "stbi_no_gif"))]
+    fn tiny_gif(frames: usize) -> Vec<u8> {
+        let mut gif = b"GIF89a\x01\x00\x01\x00\x80\x00\x00\x00\x00\x00\xff\xff\xff".to_vec();
+        for _ in 0..frames {
+            // Graphic control extension followed by the frame
+            gif.extend_from_slice(b"\x21\xf9\x04\x00\x01\x00\x00\x00");
+            gif.extend_from_slice(b"\x2c\x00\x00\x00\x00\x01\x00\x01\x00\x00\x02\x02\x44\x01\x00");
+        }
+        gif.push(0x3B);
+        gif
+    }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_gif"))]
+    fn load_gif_limits() {
+        let data = tiny_gif(64);
+        assert_eq!(chunks::gif_frame_count(&data), Some(64));
+        assert_eq!(chunks::gif_frame_count(&data[..data.len() - 20]), Some(63));
+        assert_eq!(chunks::gif_frame_count(b"GIF89a"), Some(0));
+        assert_eq!(chunks::gif_frame_count(b"\x89PNG"), None);
+
+        // Each frame is within the limit, all of them are not
+        let limits = Limits {
+            max_alloc_bytes: Some(63 * 4),
+            ..Limits::default()
+        };
+        let err =
+            stbi_load_gif_from_memory(&data, Channels::Grey, LoadOptions::new().limits(limits))
+                .err()
+                .expect("Limits must cover all frames");
+        assert!(matches!(err, Error::LimitsExceeded));
+
+        let limits = Limits {
+            max_alloc_bytes: Some(64 * 4),
+            ..Limits::default()
+        };
+        let image =
+            stbi_load_gif_from_memory(&data, Channels::Grey, LoadOptions::new().limits(limits))
+                .expect("Failed to load GIF within limits");
+        assert_eq!(image.frame_count(), 64);
+        assert!(image.frames().flatten().all(|c| *c == 0));
+    }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_gif"))]
+    fn load_flip_vertically() {
+        let data = fixture("animated.gif");
+
+        let options = LoadOptions::new().flip_vertically(true);
+        let image =
+            stbi_load_from_memory(&data, Channels::Rgb, options).expect("Failed to load image");
+        assert_eq!(&image.as_slice()[..3], &[0, 0, 255]);
+
+        // Options must not leak into subsequent loads
+        let image = stbi_load_from_memory(&data, Channels::Rgb, LoadOptions::default())
+            .expect("Failed to load image");
+        assert_eq!(&image.as_slice()[..3], &[255, 0, 0]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_gif"))]
+    fn image_accessors() {
+        let data = fixture("animated.gif");
+        let image = stbi_load_from_memory(&data, Channels::Rgb, LoadOptions::default())
+            .expect("Failed to load image");
+
+        assert_eq!(image.width(), 2);
+        assert_eq!(image.height(), 2);
+        assert_eq!(image.channels(), 3);
+        assert_eq!(image.info().components, 4);
+        assert_eq!(image.len(), 12);
+
+        let rows = image.rows().collect::<Vec<_>>();
+        assert_eq!(rows, vec![&[255, 0, 0, 255, 0, 0], &[0, 0, 255, 0, 0, 255]]);
+
+        assert_eq!(image.get_pixel(1, 0), Some(&[255, 0, 0][..]));
+        assert_eq!(image.get_pixel(0, 1), Some(&[0, 0, 255][..]));
+        assert_eq!(image.get_pixel(2, 0), None);
+        assert_eq!(image.row(2), None);
+    }
+
+    #[test]
+    fn image_view_crop() {
+        let data = (0..4 * 3 * 2).collect::<Vec<u8>>();
+        let view = ImageView::new(&data, 4, 3, 2, 8).expect("Failed to create view");
+
+        let crop = view.crop(1, 1, 2, 2).expect("Failed to crop");
+        assert_eq!(crop.width(), 2);
+        assert_eq!(crop.height(), 2);
+        assert_eq!(crop.stride(), 8);
+        assert_eq!(crop.get_pixel(0, 0), Some(&[10, 11][..]));
+        assert_eq!(crop.to_vec(), vec![10, 11, 12, 13, 18, 19, 20, 21]);
+
+        let corner = view.crop(3, 2, 1, 1).expect("Failed to crop corner");
+        assert_eq!(corner.to_vec(), vec![22, 23]);
+
+        assert!(view.crop(3, 0, 2, 1).is_none());
+        assert!(view.crop(1, 0, usize::MAX, 1).is_none());
+        assert!(view.crop(0, usize::MAX, 1, 2).is_none());
+        assert!(ImageView::new(&data, 4, 4, 2, 8).is_none());
+    }
+
+    #[test]
+    fn load_limits() {
+        let data = fixture("white.png");
+
+        let exceeded = [
+            Limits {
+                max_width: Some(19),
+                ..Limits::default()
+            },
+            Limits {
+                max_height: Some(29),
+                ..Limits::default()
+            },
+            Limits {
+                max_pixels: Some(599),
+                ..Limits::default()
+            },
+            Limits {
+                max_alloc_bytes: Some(1199),
+                ..Limits::default()
+            },
+        ];
+
+        for limits in exceeded.iter().cloned() {
+            let options = LoadOptions::new().limits(limits);
+
+            let err = stbi_load_16_from_memory(&data, Channels::Grey, options)
+                .err()
+                .expect("Limits must be checked");
+            assert!(matches!(err, Error::LimitsExceeded));
+
+            let err = stbi_load_16_from_reader(&mut &data[..], Channels::Grey, options)
+                .err()
+                .expect("Limits must be checked");
+            assert!(matches!(err, Error::LimitsExceeded));
+        }
+
+        let options = LoadOptions::new().limits(Limits {
+            max_width: Some(20),
+            max_height: Some(30),
+            max_pixels: Some(600),
+            max_alloc_bytes: Some(1200),
+        });
+
+        let image = stbi_load_16_from_memory(&data, Channels::Grey, options)
+            .expect("Failed to load image within limits");
+        assert_eq!(image.len(), 600);
+
+        // Reader must be rewound after probing
+        let image = stbi_load_16_from_reader(&mut &data[..], Channels::Grey, options)
+            .expect("Failed to load image within limits from reader");
+        assert_eq!(image.len(), 600);
+    }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_hdr"))]
+    fn is_hdr() {
+        let hdr = fixture("tiny.hdr");
+        let png = fixture("white.png");
+
+        assert!(stbi_is_hdr_from_memory(&hdr));
+        assert!(!stbi_is_hdr_from_memory(&png));
+
+        assert!(stbi_is_hdr_from_reader(&mut &hdr[..]));
+        assert!(!stbi_is_hdr_from_reader(&mut &png[..]));
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "stbi_no_linear", feature = "stbi_no_hdr")))]
+    fn load_dynamic_precision() {
+        let options = LoadOptions::default();
+
+        for from_reader in [false, true].iter().cloned() {
+            let load = |file: &str| {
+                let data = fixture(file);
+                if from_reader {
+                    load_dynamic_from_reader(&mut &data[..], Channels::Default, options)
+                } else {
+                    load_dynamic(&data, Channels::Default, options)
+                }
+                .expect("Failed to load image")
+            };
+
+            match load("white.png") {
+                DynamicImage::U8(image) => assert!(image.iter().all(|c| *c == u8::MAX)),
+                _ => panic!("Expected 8-bit image"),
+            }
+
+            match load("gray16.png") {
+                DynamicImage::U16(image) => {
+                    assert_eq!(image.as_slice(), &[0, 0xFFFF, 0x1234, 0x8000])
+                }
+                _ => panic!("Expected 16-bit image"),
+            }
+
+            match load("tiny.hdr") {
+                DynamicImage::F32(image) => {
+                    assert_eq!(image.channels(), 3);
+                    assert_eq!(image.as_slice(), &[1.0, 0.5, 0.25, 2.0, 4.0, 8.0]);
+                }
+                _ => panic!("Expected float image"),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "stbi_no_linear", feature = "stbi_no_hdr")))]
+    fn load_hdr_to_ldr() {
+        let data = fixture("tiny.hdr");
+
+        let conversion = HdrToLdr {
+            exposure: -3.0,
+            tonemap: Tonemap::Reinhard,
+            gamma: 1.0,
+            ..HdrToLdr::default()
+        };
+        let options = LoadOptions::new().hdr_to_ldr(conversion);
+
+        let image = stbi_load_from_memory(&data, Channels::RgbAlpha, options)
+            .expect("Failed to load image");
+        assert_eq!(image.as_slice(), &[28, 15, 8, 255, 51, 85, 128, 255]);
+
+        let from_reader = stbi_load_from_reader(&mut &data[..], Channels::RgbAlpha, options)
+            .expect("Failed to load image from reader");
+        assert_eq!(from_reader.as_slice(), image.as_slice());
+
+        let hdr = stbi_loadf_from_memory(&data, Channels::RgbAlpha, LoadOptions::default())
+            .expect("Failed to load float image");
+        assert_eq!(hdr.into_ldr(conversion).as_slice(), image.as_slice());
+
+        // Floats are converted in place and still handed over without copying
+        let pixels = image.as_ptr();
+        let vec = image.into_vec();
+        assert_eq!(vec.as_ptr(), pixels);
+
+        // LDR images are loaded as usual
+        let data = fixture("white.png");
+        let image =
+            stbi_load_from_memory(&data, Channels::Grey, options).expect("Failed to load image");
+        assert!(image.iter().all(|c| *c == u8::MAX));
+    }
+
+    #[test]
+    fn load_into_buffer() {
+        let data = fixture("gray16.png");
+
+        let mut dest = [0_u16; 5];
+        let info = load_into(&data, &mut dest, Channels::Default, LoadOptions::default())
+            .expect("Failed to load image into buffer");
+        assert_eq!(info.width, 2);
+        assert_eq!(info.height, 2);
+        assert_eq!(dest, [0, 0xFFFF, 0x1234, 0x8000, 0]);
+
+        let mut dest = [0_u16; 7];
+        let err = load_into(
+            &data,
+            &mut dest,
+            Channels::GreyAlpha,
+            LoadOptions::default(),
+        )
+        .expect_err("Buffer must be too small");
+        assert!(matches!(err, Error::BufferTooSmall(8)));
+    }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_jpeg"))]
+    fn load_into_in_place() {
+        // Aligned the way stb needs, with a spare byte the JPEG decoder asks for
+        #[repr(align(16))]
+        struct Aligned([u8; 16 * 8 + 1]);
+
+        let data = fixture("rotated.jpg");
+        let expected = stbi_load_from_memory(&data, Channels::Grey, LoadOptions::default())
+            .expect("Failed to load image");
+
+        let mut dest = Aligned([0; 16 * 8 + 1]);
+        let info = load_into(&data, &mut dest.0, Channels::Grey, LoadOptions::default())
+            .expect("Failed to load image into buffer");
+        assert_eq!((info.width, info.height), (16, 8));
+        assert_eq!(&dest.0[..16 * 8], expected.as_slice());
+
+        let options = LoadOptions::new().apply_orientation(true);
+        let info = load_into(&data, &mut dest.0, Channels::Grey, options)
+            .expect("Failed to load image into buffer");
+        assert_eq!((info.width, info.height), (8, 16));
+
+        let oriented =
+            stbi_load_from_memory(&data, Channels::Grey, options).expect("Failed to load image");
+        assert_eq!(&dest.0[..16 * 8], oriented.as_slice());
+    }
+
+    #[test]
+    fn concurrent_loads_keep_options() {
+        let files = [
+            "gray16.png",
+            #[cfg(not(feature = "stbi_no_hdr"))]
+            "tiny.hdr",
+        ]
+        .iter()
+        .map(|file| fixture(file))
+        .collect::<Vec<_>>();
+
+        let variants = [
+            LoadOptions::default(),
+            LoadOptions::new().flip_vertically(true),
+            LoadOptions::new()
+                .hdr_to_ldr_gamma(1.0)
+                .hdr_to_ldr_scale(0.25),
+        ];
+
+        let load = |file: &[u8], options| {
+            stbi_load_from_memory(file, Channels::Rgb, options)
+                .expect("Failed to load image")
+                .into_vec()
+        };
+
+        let expected = files
+            .iter()
+            .map(|file| {
+                variants
+                    .iter()
+                    .map(|options| load(file, *options))
+                    .collect()
+            })
+            .collect::<Vec<Vec<_>>>();
+
+        assert_ne!(expected[0][0], expected[0][1]);
+        #[cfg(not(feature = "stbi_no_hdr"))]
+        assert_ne!(expected[1][0], expected[1][2]);
+
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let (files, expected, load) = (&files, &expected, &load);
+                scope.spawn(move || {
+                    for i in 0..200 {
+                        let file = (thread + i) % files.len();
+                        let variant = (thread * 7 + i) % variants.len();
+                        assert_eq!(
+                            load(&files[file], variants[variant]),
+                            expected[file][variant]
+                        );
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_jpeg"))]
+    fn load_exif_orientation() {
+        // 16 x 8 image stored with a black left half, EXIF says it's rotated by 90 degrees
+        let data = fixture("rotated.jpg");
+        assert_eq!(jpeg_orientation(&data), Some(Orientation::Rotate90));
+
+        let image = stbi_load_from_memory(&data, Channels::Grey, LoadOptions::default())
+            .expect("Failed to load image");
+        assert_eq!((image.width(), image.height()), (16, 8));
+        assert_eq!(image.orientation(), Orientation::Rotate90);
+
+        let image = stbi_load_from_reader(&mut &data[..], Channels::Grey, LoadOptions::default())
+            .expect("Failed to load image from reader");
+        assert_eq!((image.width(), image.height()), (16, 8));
+        assert_eq!(image.orientation(), Orientation::Rotate90);
+
+        let is_black = |pixel: Option<&[u8]>| pixel.expect("Pixel out of bounds")[0] < 128;
+
+        let options = LoadOptions::new().apply_orientation(true);
+        let image =
+            stbi_load_from_memory(&data, Channels::Grey, options).expect("Failed to load image");
+        assert_eq!((image.width(), image.height()), (8, 16));
+        assert_eq!((image.info().width, image.info().height), (8, 16));
+        assert_eq!(image.orientation(), Orientation::Normal);
+        assert!(is_black(image.get_pixel(0, 0)));
+        assert!(is_black(image.get_pixel(7, 7)));
+        assert!(!is_black(image.get_pixel(0, 8)));
+        assert!(!is_black(image.get_pixel(7, 15)));
+
+        let image = stbi_load_from_reader(&mut &data[..], Channels::Grey, options)
+            .expect("Failed to load image from reader");
+        assert_eq!((image.width(), image.height()), (8, 16));
+        assert_eq!(image.orientation(), Orientation::Normal);
+        assert!(is_black(image.get_pixel(0, 0)));
+        assert!(!is_black(image.get_pixel(0, 15)));
+
+        let options = options.flip_vertically(true);
+        let image =
+            stbi_load_from_memory(&data, Channels::Grey, options).expect("Failed to load image");
+        assert_eq!((image.width(), image.height()), (8, 16));
+        assert!(!is_black(image.get_pixel(0, 0)));
+        assert!(is_black(image.get_pixel(0, 15)));
+    }
+
+    #[test]
+    fn decoder_reuse() {
+        let data = fixture("white.png");
+        let mut decoder = Decoder::default();
+        let mut dest = vec![0_u8; 600];
+
+        for _ in 0..3 {
+            dest.iter_mut().for_each(|c| *c = 0);
+            decoder
+                .load_into(&data, &mut dest, Channels::Grey)
+                .expect("Failed to decode image");
+            assert!(dest.iter().all(|c| *c == u8::MAX));
+        }
+
+        assert!(!decoder.pool.is_empty());
+
+        decoder.shrink();
+        assert!(decoder.pool.is_empty());
+    }
+
+    #[test]
+    fn load_from_path() {
+        let info = info_path(fixture_path("white.png")).expect("Failed to get image info");
+        assert_eq!(info.width, 20);
+        assert_eq!(info.height, 30);
+
+        let image = load_path::<u8>(
+            fixture_path("white.png"),
+            Channels::Grey,
+            LoadOptions::default(),
+        )
+        .expect("Failed to load image from path");
+        assert_eq!(image.len(), 600);
+        assert!(image.iter().all(|c| *c == u8::MAX));
+
+        let err = load_path::<u16>(
+            fixture_path("missing.png"),
+            Channels::Default,
+            LoadOptions::default(),
+        )
+        .err()
+        .expect("Missing file must not be loaded");
+        match err {
+            Error::Io(err) => assert_eq!(err.kind(), io::ErrorKind::NotFound),
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn load_from_mmap() {
+        let image = load_mmap::<u8>(
+            fixture_path("white.png"),
+            Channels::Grey,
+            LoadOptions::default(),
+        )
+        .expect("Failed to load mapped image");
+        assert_eq!(image.width(), 20);
+        assert_eq!(image.height(), 30);
+        assert!(image.iter().all(|c| *c == u8::MAX));
+
+        let err = load_mmap::<u8>(
+            fixture_path("missing.png"),
+            Channels::Default,
+            LoadOptions::default(),
+        )
+        .err()
+        .expect("Missing file must not be mapped");
+        assert!(matches!(err, Error::Io(_)));
+    }
+
+    #[test]
+    fn load_unknown_format() {
+        let err = stbi_load_from_memory(&[0; 32], Channels::Default, LoadOptions::default())
+            .err()
+            .expect("Garbage must not be decoded");
+
+        assert!(matches!(err, Error::UnknownFormat));
+    }
+
+    #[test]
+    fn load_corrupt() {
+        let mut data = fixture("white.png");
+        data.truncate(data.len() / 2);
+
+        let err = stbi_load_from_memory(&data, Channels::Default, LoadOptions::default())
+            .err()
+            .expect("Truncated image must not be decoded");
+
+        assert!(matches!(err, Error::Corrupt(_)));
+    }
+
+    #[test]
+    fn load_reader_error() {
+        struct Failing;
+
+        impl io::Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("boom"))
+            }
+        }
+
+        let err = stbi_load_from_reader(&mut Failing, Channels::Default, LoadOptions::default())
+            .err()
+            .expect("Failing reader must not be decoded");
+
+        match err {
+            Error::Io(err) => assert_eq!(err.to_string(), "boom"),
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn load_reader_panic() {
+        /// Serves `data` up to `limit` bytes and panics afterwards
+        struct Panicking<'a> {
+            data: &'a [u8],
+            limit: usize,
+        }
+
+        impl io::Read for Panicking<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.limit == 0 {
+                    panic!("reader panic");
+                }
+
+                let len = buf.len().min(self.limit);
+                let n = self.data.read(&mut buf[..len])?;
+                self.limit -= n;
+                Ok(n)
+            }
+        }
+
+        let data = fixture("white.png");
+
+        let payload = panic::catch_unwind(|| {
+            let mut reader = Panicking {
+                data: &data,
+                limit: 0,
+            };
+            stbi_info_from_reader(&mut reader)
+        })
+        .expect_err("Reader panic must be resumed");
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"reader panic"));
+
+        // Panic in the middle of the image data
+        let payload = panic::catch_unwind(|| {
+            let mut reader = Panicking {
+                data: &data,
+                limit: data.len() / 2,
+            };
+            stbi_load_from_reader(
+                &mut reader,
+                Channels::Default,
+                LoadOptions::new().flip_vertically(true),
+            )
+        })
+        .err()
+        .expect("Reader panic must be resumed");
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"reader panic"));
+
+        // Loader is usable after the panic
+        let image = stbi_load_from_reader(&mut &data[..], Channels::Grey, LoadOptions::default())
+            .expect("Failed to load image");
+        assert_eq!((image.width(), image.height()), (20, 30));
+    }
 }
@@ -0,0 +1,430 @@
+//! Allocation policy of stb decodes, installed as [`sys::alloc::Hooks`].
+//!
+//! Blocks allocated within [`vec_scope`] are declared so that [`take_vec`] is able to hand
+//! them over to `Vec<T>` without copying.
+//!
+//! Blocks freed while a [`Pool`] is active on the current thread are kept in the pool and
+//! reused by subsequent allocations instead of going back to the allocator.
+//!
+//! Caller memory can be lent to stb with [`lend`], so stb decodes straight into it.
+
+use stb_sys as sys;
+use std::alloc::Layout;
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::os::raw::c_void;
+use sys::alloc::{Block, Hooks, ALIGN};
+
+thread_local! {
+    /// Blocks of the pool active on this thread, if any
+    static ACTIVE: RefCell<Option<Vec<Block>>> = const { RefCell::new(None) };
+    /// Alignment new blocks are declared with, set by [`vec_scope`]
+    static VEC_ALIGN: Cell<Option<usize>> = const { Cell::new(None) };
+    /// Caller memory lent to stb, set by [`lend`]
+    static LENT: Cell<Option<Lent>> = const { Cell::new(None) };
+}
+
+const HOOKS: Hooks = Hooks { alloc, free };
+
+fn alloc(size: usize) -> Option<Block> {
+    lend_out(size).or_else(|| take_pooled(size)).or_else(|| {
+        let align = VEC_ALIGN.try_with(Cell::get).ok().flatten()?;
+        Block::alloc(size, align)
+    })
+}
+
+fn free(block: Block) -> Option<Block> {
+    if give_back(block.ptr) {
+        None
+    } else {
+        put_pooled(block)
+    }
+}
+
+/// Runs `f` with stb allocations going through [`HOOKS`]
+fn hooked<R>(f: impl FnOnce() -> R) -> R {
+    // SAFETY: lent memory is the only one not owned by the allocator, it's never returned by
+    // `free` and callers take it back when stb returns it
+    unsafe { sys::alloc::with_hooks(HOOKS, f) }
+}
+
+/// Runs `f` with blocks allocated so that [`take_vec`] is able to hand them over to `Vec<T>`.
+///
+/// Blocks stay aligned the way stb needs. Nested scopes keep the element type of the
+/// outermost one, so buffers converted in place into the outer type can be taken over too.
+pub fn vec_scope<T, R>(f: impl FnOnce() -> R) -> R {
+    /// Restores the previous alignment, even if `f` panics
+    struct Guard(Option<usize>);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            VEC_ALIGN.with(|align| align.set(self.0));
+        }
+    }
+
+    let previous = VEC_ALIGN.with(|align| {
+        let previous = align.get();
+        align.set(previous.or(Some(mem::align_of::<T>())));
+        previous
+    });
+    let _guard = Guard(previous);
+
+    hooked(f)
+}
+
+/// Takes block `p` holding `len` elements as a vector without copying.
+/// Gives `p` back if the block can't be freed by `Vec<T>`, which is the case for blocks
+/// allocated outside of [`vec_scope`] of `T`.
+///
+/// # Safety
+///
+/// `p` must be returned by stb and not freed yet, its first `len` elements must be initialized.
+/// If a vector is returned, `p` must not be used or freed afterwards.
+pub unsafe fn take_vec<T>(p: *mut T, len: usize) -> Result<Vec<T>, *mut T> {
+    let size = mem::size_of::<T>();
+
+    let layout = match sys::alloc::layout(p as *mut c_void) {
+        Some(layout) if size > 0 && !is_lent(p as *mut u8) => layout,
+        _ => return Err(p),
+    };
+
+    // `Vec<T>` frees its buffer with the layout of `[T; capacity]`
+    let capacity = layout.size() / size;
+    if layout.align() != mem::align_of::<T>() || capacity * size != layout.size() || capacity < len
+    {
+        return Err(p);
+    }
+
+    sys::alloc::take(p as *mut c_void);
+    Ok(Vec::from_raw_parts(p, len, capacity))
+}
+
+/// Caller memory serving a single stb allocation at a time
+#[derive(Copy, Clone)]
+struct Lent {
+    ptr: *mut u8,
+    min_size: usize,
+    size: usize,
+    in_use: bool,
+}
+
+/// Runs `f` with `dest` lent to stb: an allocation of `min_size` up to `dest` size bytes is
+/// served from `dest` instead of the allocator, so stb writes its output straight into it.
+///
+/// Callers tell whether output was decoded in place by comparing the pointer returned by stb
+/// with `dest`. `dest` isn't lent unless it's aligned the way stb needs.
+///
+/// # Safety
+///
+/// Any bytes must be a valid `T`. If stb returns `dest`, it must be taken back with
+/// [`sys::alloc::take`] rather than freed.
+pub unsafe fn lend<T, R>(dest: &mut [T], min_size: usize, f: impl FnOnce() -> R) -> R {
+    /// Restores the previously lent memory, even if `f` panics
+    struct Guard(Option<Lent>);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            LENT.with(|lent| lent.set(self.0));
+        }
+    }
+
+    let ptr = dest.as_mut_ptr() as *mut u8;
+    let size = mem::size_of_val(dest);
+    let lent = Lent {
+        ptr,
+        min_size,
+        size,
+        in_use: false,
+    };
+
+    let lent = Some(lent).filter(|_| size > 0 && (ptr as usize & (ALIGN - 1)) == 0);
+    let _guard = Guard(LENT.with(|current| current.replace(lent)));
+
+    hooked(f)
+}
+
+/// Serves an allocation of `size` bytes from lent memory if it's free and `size` fits
+fn lend_out(size: usize) -> Option<Block> {
+    LENT.try_with(|lent| {
+        let mut current = lent.get()?;
+        if current.in_use || size < current.min_size || size > current.size {
+            return None;
+        }
+
+        current.in_use = true;
+        lent.set(Some(current));
+        Some(Block {
+            ptr: current.ptr,
+            layout: Layout::from_size_align(current.size, ALIGN).ok()?,
+        })
+    })
+    .ok()
+    .flatten()
+}
+
+/// Returns `true` if `p` points to lent memory in use
+fn is_lent(p: *mut u8) -> bool {
+    LENT.try_with(|lent| lent.get().is_some_and(|lent| lent.in_use && lent.ptr == p))
+        .unwrap_or(false)
+}
+
+/// Takes lent memory back from stb, returns `false` if `p` doesn't point to it
+fn give_back(p: *mut u8) -> bool {
+    LENT.try_with(|lent| match lent.get() {
+        Some(mut current) if current.in_use && current.ptr == p => {
+            current.in_use = false;
+            lent.set(Some(current));
+            true
+        }
+        _ => false,
+    })
+    .unwrap_or(false)
+}
+
+/// Cache of memory blocks reused by stb allocations.
+///
+/// Decoding the same kind of images over and over again makes stb allocate and free the same
+/// set of blocks each time. Running decodes within [`Pool::scope`] keeps freed blocks around,
+/// so after the first decode allocations are served from the pool.
+#[derive(Default)]
+pub struct Pool {
+    blocks: Vec<Block>,
+}
+
+// Blocks are plain memory not tied to any thread
+unsafe impl Send for Pool {}
+
+impl Pool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `f` with this pool active on the current thread
+    pub fn scope<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        /// Puts blocks back to the pool, even if `f` panics
+        struct Guard<'a> {
+            pool: &'a mut Pool,
+            previous: Option<Vec<Block>>,
+        }
+
+        impl Drop for Guard<'_> {
+            fn drop(&mut self) {
+                let previous = self.previous.take();
+                let blocks =
+                    ACTIVE.with(|active| mem::replace(&mut *active.borrow_mut(), previous));
+                self.pool.blocks = blocks.unwrap_or_default();
+            }
+        }
+
+        let blocks = mem::take(&mut self.blocks);
+        let previous = ACTIVE.with(|active| active.borrow_mut().replace(blocks));
+        let _guard = Guard {
+            pool: self,
+            previous,
+        };
+
+        hooked(f)
+    }
+
+    /// Releases all cached blocks
+    pub fn clear(&mut self) {
+        for block in self.blocks.drain(..) {
+            // SAFETY: pooled blocks are allocated by the allocator, lent memory is never pooled
+            unsafe { block.dealloc() }
+        }
+    }
+
+    /// Returns the number of cached blocks
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Takes the smallest block of at least `size` bytes from the active pool
+fn take_pooled(size: usize) -> Option<Block> {
+    ACTIVE
+        .try_with(|active| {
+            let mut active = active.borrow_mut();
+            let blocks = active.as_mut()?;
+
+            let index = blocks
+                .iter()
+                .enumerate()
+                .filter(|(_, block)| block.capacity() >= size)
+                .min_by_key(|(_, block)| block.capacity())
+                .map(|(index, _)| index)?;
+
+            Some(blocks.swap_remove(index))
+        })
+        .ok()
+        .flatten()
+}
+
+/// Returns `block` to the active pool, gives it back if there is none
+fn put_pooled(block: Block) -> Option<Block> {
+    let mut block = Some(block);
+
+    let _ = ACTIVE.try_with(|active| {
+        if let Some(blocks) = active.borrow_mut().as_mut() {
+            blocks.extend(block.take());
+        }
+    });
+
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+    use sys::alloc::{free, malloc, realloc};
+
+    #[test]
+    fn pool_reuses_blocks() {
+        let mut pool = Pool::new();
+
+        let first = pool.scope(|| unsafe {
+            let p = malloc(100);
+            free(p);
+            p
+        });
+        assert_eq!(pool.len(), 1);
+
+        let (second, larger) = pool.scope(|| unsafe {
+            let second = malloc(50);
+            let larger = malloc(200);
+            free(second);
+            free(larger);
+            (second, larger)
+        });
+
+        assert_eq!(first, second);
+        assert_ne!(first, larger);
+        assert_eq!(pool.len(), 2);
+
+        // Blocks freed outside of the scope go back to the allocator
+        unsafe { free(malloc(10)) };
+        assert_eq!(pool.len(), 2);
+
+        pool.clear();
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn nested_pools() {
+        let mut outer = Pool::new();
+        let mut inner = Pool::new();
+
+        outer.scope(|| unsafe {
+            inner.scope(|| free(malloc(10)));
+            free(malloc(20));
+        });
+
+        assert_eq!(outer.len(), 1);
+        assert_eq!(inner.len(), 1);
+    }
+
+    #[test]
+    fn vec_handover() {
+        unsafe {
+            let p = vec_scope::<u16, _>(|| {
+                let p = malloc(4) as *mut u16;
+                assert_eq!(p as usize % ALIGN, 0);
+                // Grown blocks keep the declared alignment
+                realloc(p as *mut c_void, 8) as *mut u16
+            });
+            ptr::copy_nonoverlapping([1_u16, 2, 3].as_ptr(), p, 3);
+
+            let vec = take_vec(p, 3).expect("Block must be handed over");
+            assert_eq!(vec.as_ptr(), p);
+            assert_eq!(vec, [1, 2, 3]);
+            assert_eq!(vec.capacity(), 4);
+
+            // Blocks allocated for other types or outside of the scope are kept
+            let p = vec_scope::<u8, _>(|| vec_scope::<f32, _>(|| malloc(8)));
+            assert!(take_vec(p as *mut f32, 2).is_err());
+            let vec = take_vec(p as *mut u8, 8).expect("Outer scope must win");
+            drop(vec);
+
+            let p = malloc(8);
+            assert_eq!(take_vec(p as *mut u8, 8), Err(p as *mut u8));
+            assert!(take_vec(p as *mut u8, 9).is_err());
+            free(p);
+
+            assert!(take_vec(ptr::null_mut::<u8>(), 0).is_err());
+        }
+    }
+
+    #[test]
+    fn lend_memory() {
+        #[repr(align(16))]
+        struct Aligned([u8; 64]);
+        let mut dest = Aligned([0; 64]);
+        let lent = dest.0.as_mut_ptr() as *mut c_void;
+
+        unsafe {
+            lend(&mut dest.0, 32, || {
+                // Too small or too large allocations aren't served from lent memory
+                let small = malloc(16);
+                let large = malloc(65);
+                assert_ne!(small, lent);
+                assert_ne!(large, lent);
+                free(small);
+                free(large);
+
+                let p = malloc(48);
+                assert_eq!(p, lent);
+                let other = malloc(48);
+                assert_ne!(other, lent, "Memory is lent once at a time");
+                free(other);
+
+                // Freed lent memory can be lent again
+                free(p);
+                let p = malloc(64);
+                assert_eq!(p, lent);
+                assert_eq!(realloc(p, 60), lent);
+                assert!(
+                    take_vec(p as *mut u8, 60).is_err(),
+                    "Lent memory isn't owned"
+                );
+
+                // Growing moves the contents into a block
+                *(p as *mut u8) = 7;
+                let moved = realloc(p, 128);
+                assert_ne!(moved, lent);
+                assert_eq!(*(moved as *mut u8), 7);
+                free(moved);
+
+                // Lent memory returned by stb is taken back
+                let p = malloc(32);
+                assert_eq!(p, lent);
+                assert!(sys::alloc::take(p).is_some());
+            });
+
+            // Memory is lent within the scope only
+            let p = malloc(48);
+            assert_ne!(p, lent);
+            free(p);
+
+            // Unaligned memory isn't lent
+            let unaligned = (lent as *mut u8).add(1) as *mut c_void;
+            lend(&mut dest.0[1..], 32, || {
+                let p = malloc(48);
+                assert_ne!(p, unaligned);
+                free(p);
+            });
+        }
+    }
+}
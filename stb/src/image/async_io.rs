@@ -0,0 +1,211 @@
+//! Loading images from tokio's `AsyncRead` (requires `async` feature).
+//!
+//! Sources are read asynchronously, decoding runs on tokio's blocking pool so it doesn't stall
+//! the runtime.
+
+use super::{detect_format, load_from_memory, stbi_info_from_memory, Channels, Error, Image};
+use super::{ImageFormat, Info, LoadOptions, Result, Sample};
+use std::io;
+use std::os::raw;
+use std::panic;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::task;
+
+/// Number of bytes first read looking for the image header, doubled each time it's not enough
+const PROBE_SIZE: usize = 4096;
+
+/// Most bytes read looking for the image header, enough for JPEG EXIF thumbnails and ICC
+/// profiles preceding the frame header
+const MAX_PROBE_SIZE: usize = 1 << 20;
+
+/// Largest source stb is able to decode
+const MAX_SOURCE_SIZE: usize = raw::c_int::MAX as usize;
+
+/// Reads up to `size` more bytes into `buffer`, returns `true` if the reader reached the end
+async fn read_more<R>(reader: &mut R, buffer: &mut Vec<u8>, size: usize) -> io::Result<bool>
+where
+    R: AsyncRead + Unpin,
+{
+    let read = reader.take(size as u64).read_to_end(buffer).await?;
+    Ok(read < size)
+}
+
+/// Reads from `reader` into `buffer` until stb is able to parse the image header
+async fn read_header<R>(reader: &mut R, buffer: &mut Vec<u8>) -> Result<Info>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut size = PROBE_SIZE;
+
+    loop {
+        let eof = read_more(reader, buffer, size).await?;
+
+        match stbi_info_from_memory(buffer) {
+            Ok(info) => return Ok(info),
+            Err(err) if eof || buffer.len() >= MAX_PROBE_SIZE => return Err(err),
+            // stb reports truncated headers as unknown format too, so check the signature
+            // to tell whether reading more may help
+            Err(err) if !detect_format(buffer).is_some_and(ImageFormat::is_enabled) => {
+                return Err(err)
+            }
+            Err(_) => size = buffer.len().min(MAX_PROBE_SIZE - buffer.len()),
+        }
+    }
+}
+
+/// Load image from `reader`.
+/// The whole source is read into memory first, then it's decoded on the blocking pool.
+///
+/// If any [`Limits`](super::Limits) are set, the image header is read first and the image is
+/// refused before the rest of the source is read. Sources larger than `i32::MAX` bytes, which
+/// stb can't decode, are refused with `Error::TooLarge`.
+pub async fn load_async<T, R>(
+    mut reader: R,
+    desired_channels: Channels,
+    options: LoadOptions,
+) -> Result<Image<T>>
+where
+    T: Sample + Send + 'static,
+    R: AsyncRead + Unpin,
+{
+    let mut buffer = Vec::new();
+
+    if !options.limits.is_unlimited() {
+        let info = read_header(&mut reader, &mut buffer).await?;
+        options.limits.check::<T>(info, desired_channels)?;
+    }
+
+    let rest = MAX_SOURCE_SIZE + 1 - buffer.len();
+    reader.take(rest as u64).read_to_end(&mut buffer).await?;
+
+    if buffer.len() > MAX_SOURCE_SIZE {
+        return Err(Error::TooLarge);
+    }
+
+    let decode = task::spawn_blocking(move || {
+        load_from_memory(&buffer, desired_channels, options, T::MEMORY_LOADER)
+    });
+
+    match decode.await {
+        Ok(result) => result,
+        Err(err) if err.is_panic() => panic::resume_unwind(err.into_panic()),
+        Err(err) => Err(Error::Io(io::Error::other(err))),
+    }
+}
+
+/// Get image dimensions & components from `reader` without fully decoding.
+/// Only as much of the source as needed to parse the header is read, up to 1 MiB.
+pub async fn info_async<R>(mut reader: R) -> Result<Info>
+where
+    R: AsyncRead + Unpin,
+{
+    read_header(&mut reader, &mut Vec::new()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::test_util::fixture;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::ReadBuf;
+
+    #[tokio::test]
+    async fn load_from_async_reader() {
+        let data = fixture("white.png");
+
+        let info = info_async(&data[..])
+            .await
+            .expect("Failed to get image info");
+        assert_eq!(info.width, 20);
+        assert_eq!(info.height, 30);
+
+        let image = load_async::<u8, _>(&data[..], Channels::Grey, LoadOptions::default())
+            .await
+            .expect("Failed to load image");
+        assert_eq!((image.width(), image.height()), (20, 30));
+        assert!(image.iter().all(|c| *c == u8::MAX));
+    }
+
+    #[tokio::test]
+    async fn load_async_errors() {
+        let err = load_async::<u8, _>(
+            &b"not an image"[..],
+            Channels::Default,
+            LoadOptions::default(),
+        )
+        .await
+        .err()
+        .expect("Unknown data must not be decoded");
+        assert!(matches!(err, Error::UnknownFormat));
+
+        struct Failing;
+
+        impl AsyncRead for Failing {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                _cx: &mut Context,
+                _buf: &mut ReadBuf,
+            ) -> Poll<io::Result<()>> {
+                Poll::Ready(Err(io::Error::other("boom")))
+            }
+        }
+
+        match info_async(Failing).await {
+            Err(Error::Io(err)) => assert_eq!(err.to_string(), "boom"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn bounded_reads() {
+        // Endless data without known signature
+        let err = info_async(tokio::io::repeat(0))
+            .await
+            .expect_err("Unknown data must not be probed");
+        assert!(matches!(err, Error::UnknownFormat));
+
+        // Endless PNG chunks without header
+        #[cfg(not(feature = "stbi_no_png"))]
+        {
+            let png = (&b"\x89PNG\r\n\x1a\n"[..]).chain(tokio::io::repeat(0));
+            info_async(png).await.expect_err("Header must not be found");
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(not(feature = "stbi_no_png"))]
+    async fn load_async_limits() {
+        use crate::image::test_util::png;
+        use crate::image::Limits;
+
+        let data = fixture("white.png");
+
+        // Header is checked before endless data following it is read
+        let endless = (&data[..33]).chain(tokio::io::repeat(0));
+        let options = LoadOptions::new().limits(Limits {
+            max_pixels: Some(20 * 30 - 1),
+            ..Limits::default()
+        });
+        let err = load_async::<u8, _>(endless, Channels::Grey, options)
+            .await
+            .err()
+            .expect("Image exceeds limits");
+        assert!(matches!(err, Error::LimitsExceeded));
+
+        // Metadata is not a part of the decoded size
+        let text = vec![b'a'; 64 << 10];
+        let mut large = data[..33].to_vec();
+        large.extend_from_slice(&png(&[(b"tEXt", &text)])[8..]);
+        large.extend_from_slice(&data[33..]);
+
+        let options = LoadOptions::new().limits(Limits {
+            max_alloc_bytes: Some(20 * 30),
+            ..Limits::default()
+        });
+        let image = load_async::<u8, _>(&large[..], Channels::Grey, options)
+            .await
+            .expect("Failed to load image");
+        assert_eq!((image.width(), image.height()), (20, 30));
+    }
+}
@@ -0,0 +1,113 @@
+//! Parallel decoding of many images at once (requires `rayon` feature).
+//!
+//! Images are decoded on the rayon thread pool and results are returned in the same order
+//! as inputs. Each decode applies `LoadOptions` on its worker thread only, so batches with
+//! different options may run concurrently with each other and with regular loaders.
+
+use super::{
+    load_from_memory as load_buffer, load_path, Channels, Image, LoadOptions, Result, Sample,
+};
+use rayon::prelude::*;
+use std::path::Path;
+
+/// Decode images from `buffers` in parallel, the result at index `i` belongs to `buffers[i]`
+pub fn load_from_memory<T, B>(
+    buffers: &[B],
+    desired_channels: Channels,
+    options: LoadOptions,
+) -> Vec<Result<Image<T>>>
+where
+    T: Sample + Send,
+    B: AsRef<[u8]> + Sync,
+{
+    buffers
+        .par_iter()
+        .map(|buffer| load_buffer(buffer.as_ref(), desired_channels, options, T::MEMORY_LOADER))
+        .collect()
+}
+
+/// Load images from files at `paths` in parallel, the result at index `i` belongs to `paths[i]`.
+/// See [`load_path`](super::load_path).
+pub fn load_paths<T, P>(
+    paths: &[P],
+    desired_channels: Channels,
+    options: LoadOptions,
+) -> Vec<Result<Image<T>>>
+where
+    T: Sample + Send,
+    P: AsRef<Path> + Sync,
+{
+    paths
+        .par_iter()
+        .map(|path| load_path(path, desired_channels, options))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::test_util::{fixture, fixture_path};
+    use crate::image::Error;
+
+    #[test]
+    fn load_in_order() {
+        let white = fixture("white.png");
+        let gray16 = fixture("gray16.png");
+        let buffers = vec![white, b"not an image".to_vec(), gray16];
+
+        let images = load_from_memory::<u8, _>(&buffers, Channels::Default, LoadOptions::default());
+        assert_eq!(images.len(), 3);
+
+        let white = images[0].as_ref().expect("Failed to load first image");
+        assert_eq!((white.width(), white.height()), (20, 30));
+
+        assert!(matches!(images[1], Err(Error::UnknownFormat)));
+
+        let gray16 = images[2].as_ref().expect("Failed to load third image");
+        assert_eq!((gray16.width(), gray16.height()), (2, 2));
+    }
+
+    #[test]
+    fn load_paths_in_order() {
+        let paths = [
+            fixture_path("gray16.png"),
+            fixture_path("missing.png"),
+            fixture_path("white.png"),
+        ];
+
+        let images = load_paths::<u16, _>(&paths, Channels::Grey, LoadOptions::default());
+        assert_eq!(images.len(), 3);
+
+        let gray16 = images[0].as_ref().expect("Failed to load first image");
+        assert_eq!(gray16.as_slice(), &[0, 0xFFFF, 0x1234, 0x8000]);
+
+        assert!(matches!(images[1], Err(Error::Io(_))));
+
+        let white = images[2].as_ref().expect("Failed to load third image");
+        assert_eq!(white.len(), 600);
+    }
+
+    #[test]
+    fn concurrent_batches_keep_options() {
+        let gray16 = fixture("gray16.png");
+        let buffers = vec![gray16; 64];
+
+        let (plain, flipped) = rayon::join(
+            || load_from_memory::<u16, _>(&buffers, Channels::Grey, LoadOptions::default()),
+            || {
+                let options = LoadOptions::new().flip_vertically(true);
+                load_from_memory::<u16, _>(&buffers, Channels::Grey, options)
+            },
+        );
+
+        for image in plain {
+            let image = image.expect("Failed to load image");
+            assert_eq!(image.as_slice(), &[0, 0xFFFF, 0x1234, 0x8000]);
+        }
+
+        for image in flipped {
+            let image = image.expect("Failed to load image");
+            assert_eq!(image.as_slice(), &[0x1234, 0x8000, 0, 0xFFFF]);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Walkers over PNG chunks, JPEG segments and GIF blocks, used to read metadata stb skips.
+
+use super::Error;
+use std::io::{self, Read};
+
+#[cfg(not(feature = "stbi_no_gif"))]
+const GIF_IMAGE: u8 = 0x2C;
+#[cfg(not(feature = "stbi_no_gif"))]
+const GIF_EXTENSION: u8 = 0x21;
+
+#[cfg(not(feature = "stbi_no_png"))]
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// JPEG walker is used for EXIF metadata even if stb doesn't decode JPEG images
+pub(super) const JPEG_SOI: u8 = 0xD8;
+pub(super) const JPEG_EOI: u8 = 0xD9;
+const JPEG_SOS: u8 = 0xDA;
+const JPEG_APP0: u8 = 0xE0;
+pub(super) const JPEG_APP1: u8 = 0xE1;
+#[cfg(not(feature = "stbi_no_jpeg"))]
+pub(super) const JPEG_APP2: u8 = 0xE2;
+const JPEG_APP15: u8 = 0xEF;
+
+/// Iterator over chunks of a PNG image, yields chunk type and data and stops after `IEND`.
+/// Chunk CRCs are not verified, same as stb does.
+#[cfg(not(feature = "stbi_no_png"))]
+pub(super) struct PngChunks<'a> {
+    data: &'a [u8],
+    done: bool,
+}
+
+/// Returns chunks of the PNG image in `buffer`, `None` if it has no PNG signature
+#[cfg(not(feature = "stbi_no_png"))]
+pub(super) fn png_chunks(buffer: &[u8]) -> Option<PngChunks<'_>> {
+    buffer
+        .strip_prefix(PNG_SIGNATURE)
+        .map(|data| PngChunks { data, done: false })
+}
+
+#[cfg(not(feature = "stbi_no_png"))]
+impl<'a> Iterator for PngChunks<'a> {
+    type Item = Result<([u8; 4], &'a [u8]), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let chunk = self.data.get(..8).and_then(|header| {
+            let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+            let kind = [header[4], header[5], header[6], header[7]];
+
+            // Chunk data is followed by 4 bytes of CRC
+            let end = len.checked_add(12)?;
+            let data = self.data.get(8..8 + len)?;
+            let rest = self.data.get(end..)?;
+
+            Some((kind, data, rest))
+        });
+
+        match chunk {
+            Some((kind, data, rest)) => {
+                self.data = rest;
+                self.done = &kind == b"IEND";
+                Some(Ok((kind, data)))
+            }
+            None => {
+                self.done = true;
+                Some(Err(Error::Corrupt("bad png chunk")))
+            }
+        }
+    }
+}
+
+/// Iterator over application (APPn) segments of a JPEG image preceding the scan data, yields
+/// segment marker and payload. Other segments are skipped without being buffered.
+pub(super) struct JpegSegments<R> {
+    reader: R,
+    done: bool,
+}
+
+/// Returns application segments of the JPEG image read from `reader`, `None` if it doesn't
+/// start with the SOI marker. Only the first two bytes are read in that case.
+pub(super) fn jpeg_segments<R: Read>(mut reader: R) -> Option<JpegSegments<R>> {
+    let mut marker = [0_u8; 2];
+    reader.read_exact(&mut marker).ok()?;
+
+    if marker == [0xFF, JPEG_SOI] {
+        Some(JpegSegments {
+            reader,
+            done: false,
+        })
+    } else {
+        None
+    }
+}
+
+impl<R: Read> JpegSegments<R> {
+    /// Reads up to the next application segment, `None` once the scan data or EOI is reached
+    fn read_segment(&mut self) -> io::Result<Option<(u8, Vec<u8>)>> {
+        let mut byte = [0_u8; 1];
+
+        loop {
+            self.reader.read_exact(&mut byte)?;
+            if byte[0] != 0xFF {
+                return Err(io::ErrorKind::InvalidData.into());
+            }
+
+            // Markers might be preceded by any number of fill bytes
+            while byte[0] == 0xFF {
+                self.reader.read_exact(&mut byte)?;
+            }
+
+            let marker = byte[0];
+            match marker {
+                JPEG_SOS | JPEG_EOI => return Ok(None),
+                // Markers without payload
+                0x01 | 0xD0..=0xD7 => continue,
+                _ => {}
+            }
+
+            // Segment length includes the length field itself
+            let mut len = [0_u8; 2];
+            self.reader.read_exact(&mut len)?;
+            let len = u16::from_be_bytes(len)
+                .checked_sub(2)
+                .ok_or(io::ErrorKind::InvalidData)? as u64;
+
+            let mut segment = self.reader.by_ref().take(len);
+            if (JPEG_APP0..=JPEG_APP15).contains(&marker) {
+                let mut payload = Vec::with_capacity(len as usize);
+                segment.read_to_end(&mut payload)?;
+                if payload.len() as u64 == len {
+                    return Ok(Some((marker, payload)));
+                }
+            } else if io::copy(&mut segment, &mut io::sink())? == len {
+                continue;
+            }
+
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+    }
+}
+
+impl<R: Read> Iterator for JpegSegments<R> {
+    type Item = Result<(u8, Vec<u8>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.read_segment() {
+            Ok(Some(segment)) => Some(Ok(segment)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(match err.kind() {
+                    io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                        Error::Corrupt("bad jpeg segment")
+                    }
+                    _ => Error::Io(err),
+                }))
+            }
+        }
+    }
+}
+
+/// Counts frames of the GIF image in `buffer` without decoding them, `None` if it has no GIF
+/// signature. Counting stops at the trailer or at the first malformed block, a truncated
+/// frame is still counted as stb allocates memory for it.
+#[cfg(not(feature = "stbi_no_gif"))]
+pub(super) fn gif_frame_count(buffer: &[u8]) -> Option<usize> {
+    if !buffer.starts_with(b"GIF87a") && !buffer.starts_with(b"GIF89a") {
+        return None;
+    }
+
+    // Size of the color table following a block with `flags`, if any
+    let color_table = |flags: u8| {
+        if flags & 0x80 != 0 {
+            3 << ((flags & 7) + 1)
+        } else {
+            0
+        }
+    };
+
+    // Header is followed by the logical screen descriptor
+    let mut pos = match buffer.get(10) {
+        Some(flags) => 13 + color_table(*flags),
+        None => return Some(0),
+    };
+    let mut frames = 0;
+
+    loop {
+        match buffer.get(pos) {
+            Some(&GIF_IMAGE) => {
+                frames += 1;
+
+                // Image descriptor is followed by the LZW code size and data sub-blocks
+                pos += match buffer.get(pos + 9) {
+                    Some(flags) => 10 + color_table(*flags) + 1,
+                    None => break,
+                };
+            }
+            // Extension label is followed by data sub-blocks
+            Some(&GIF_EXTENSION) => pos += 2,
+            _ => break,
+        }
+
+        // Skip data sub-blocks up to the terminating empty one
+        loop {
+            match buffer.get(pos) {
+                Some(0) => {
+                    pos += 1;
+                    break;
+                }
+                Some(len) => pos += 1 + *len as usize,
+                None => return Some(frames),
+            }
+        }
+    }
+
+    Some(frames)
+}
@@ -0,0 +1,302 @@
+//! Color space metadata of PNG and JPEG images.
+//! stb decodes pixels as stored and skips color management chunks, so they're read here.
+
+#[cfg(any(not(feature = "stbi_no_png"), not(feature = "stbi_no_jpeg")))]
+use super::{chunks, ImageFormat};
+use super::{detect_format, Error, Result};
+
+/// Color space metadata declared by the image file.
+///
+/// All fields are `None` if the image declares nothing, in which case it's usually assumed
+/// to be sRGB.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ColorInfo {
+    /// Embedded ICC profile (PNG `iCCP` chunk or JPEG APP2 `ICC_PROFILE` segments), decompressed
+    pub icc_profile: Option<Vec<u8>>,
+    /// Rendering intent of the PNG `sRGB` chunk, image is in sRGB color space if present
+    pub srgb: Option<RenderingIntent>,
+    /// Gamma of the PNG `gAMA` chunk as stored, e.g. `0.45455` for images encoded with 1/2.2
+    pub gamma: Option<f32>,
+    /// Primaries and white point of the PNG `cHRM` chunk
+    pub chromaticities: Option<Chromaticities>,
+}
+
+/// Rendering intent defined by ICC
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual = 0,
+    RelativeColorimetric = 1,
+    Saturation = 2,
+    AbsoluteColorimetric = 3,
+}
+
+impl RenderingIntent {
+    #[cfg(not(feature = "stbi_no_png"))]
+    fn from_u8(value: u8) -> Option<Self> {
+        let intent = match value {
+            0 => RenderingIntent::Perceptual,
+            1 => RenderingIntent::RelativeColorimetric,
+            2 => RenderingIntent::Saturation,
+            3 => RenderingIntent::AbsoluteColorimetric,
+            _ => return None,
+        };
+
+        Some(intent)
+    }
+}
+
+/// CIE 1931 xy coordinates of the white point and RGB primaries
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Chromaticities {
+    pub white: (f32, f32),
+    pub red: (f32, f32),
+    pub green: (f32, f32),
+    pub blue: (f32, f32),
+}
+
+/// Get color space metadata from a slice without decoding pixels.
+///
+/// PNG and JPEG images are inspected, other formats have no color metadata stb is aware of,
+/// so an empty `ColorInfo` is returned for them.
+pub fn color_info_from_memory(buffer: &[u8]) -> Result<ColorInfo> {
+    match detect_format(buffer) {
+        Some(format) if !format.is_enabled() => Err(Error::UnknownFormat),
+        #[cfg(not(feature = "stbi_no_png"))]
+        Some(ImageFormat::Png) => png_color_info(buffer),
+        #[cfg(not(feature = "stbi_no_jpeg"))]
+        Some(ImageFormat::Jpeg) => jpeg_color_info(buffer),
+        Some(_) => Ok(ColorInfo::default()),
+        None => Err(Error::UnknownFormat),
+    }
+}
+
+/// PNG stores gamma and chromaticities as integers scaled by 100000
+#[cfg(not(feature = "stbi_no_png"))]
+fn png_fixed_point(data: &[u8], index: usize) -> f32 {
+    let offset = index * 4;
+    let bytes = [
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ];
+    u32::from_be_bytes(bytes) as f32 / 100_000.0
+}
+
+/// Largest decompressed `iCCP` profile, real profiles rarely exceed a few hundred kilobytes
+#[cfg(not(feature = "stbi_no_png"))]
+const MAX_ICC_PROFILE_LEN: usize = 16 << 20;
+
+#[cfg(not(feature = "stbi_no_png"))]
+fn png_color_info(buffer: &[u8]) -> Result<ColorInfo> {
+    use crate::zlib;
+
+    let mut info = ColorInfo::default();
+
+    for chunk in chunks::png_chunks(buffer).ok_or(Error::UnknownFormat)? {
+        let (kind, data) = chunk?;
+
+        match &kind {
+            b"iCCP" => {
+                // Profile name, null separator, compression method and zlib stream
+                let name = data
+                    .iter()
+                    .position(|byte| *byte == 0)
+                    .ok_or(Error::Corrupt("bad iCCP chunk"))?;
+
+                match data.get(name + 1..) {
+                    Some([0, profile @ ..]) => {
+                        let profile =
+                            zlib::inflate_limited(profile, MAX_ICC_PROFILE_LEN).map_err(|err| {
+                                match err {
+                                    zlib::Error::BufferTooSmall => Error::LimitsExceeded,
+                                    zlib::Error::OutOfMemory => Error::OutOfMemory,
+                                    _ => Error::Corrupt("bad iCCP profile"),
+                                }
+                            })?;
+                        info.icc_profile = Some(profile);
+                    }
+                    _ => return Err(Error::Corrupt("bad iCCP chunk")),
+                }
+            }
+            b"sRGB" if data.len() == 1 => info.srgb = RenderingIntent::from_u8(data[0]),
+            b"gAMA" if data.len() == 4 => info.gamma = Some(png_fixed_point(data, 0)),
+            b"cHRM" if data.len() == 32 => {
+                let point = |index| {
+                    (
+                        png_fixed_point(data, index),
+                        png_fixed_point(data, index + 1),
+                    )
+                };
+                info.chromaticities = Some(Chromaticities {
+                    white: point(0),
+                    red: point(2),
+                    green: point(4),
+                    blue: point(6),
+                });
+            }
+            // Color chunks must precede image data
+            b"IDAT" => break,
+            _ => {}
+        }
+    }
+
+    Ok(info)
+}
+
+#[cfg(not(feature = "stbi_no_jpeg"))]
+fn jpeg_color_info(buffer: &[u8]) -> Result<ColorInfo> {
+    const ICC_HEADER: &[u8] = b"ICC_PROFILE\0";
+
+    // Profiles larger than a segment are split into numbered chunks
+    let mut parts = Vec::new();
+    let mut count = 0;
+
+    for segment in chunks::jpeg_segments(buffer).ok_or(Error::UnknownFormat)? {
+        let (marker, payload) = segment?;
+        if marker != chunks::JPEG_APP2 {
+            continue;
+        }
+
+        if let Some([index, total, data @ ..]) = payload.strip_prefix(ICC_HEADER) {
+            count = *total;
+            parts.push((*index, data.to_vec()));
+        }
+    }
+
+    let mut info = ColorInfo::default();
+    if parts.is_empty() {
+        return Ok(info);
+    }
+
+    parts.sort_by_key(|(index, _)| *index);
+    let complete = parts.len() == count as usize
+        && parts
+            .iter()
+            .enumerate()
+            .all(|(position, (index, _))| *index as usize == position + 1);
+    if !complete {
+        return Err(Error::Corrupt("bad ICC profile"));
+    }
+
+    info.icc_profile = Some(parts.into_iter().flat_map(|(_, data)| data).collect());
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "stbi_no_png"))]
+    use crate::image::test_util::{png, zlib_runs};
+
+    #[cfg(not(feature = "stbi_no_jpeg"))]
+    fn jpeg(segments: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+        for (marker, payload) in segments {
+            data.extend_from_slice(&[0xFF, *marker]);
+            data.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+            data.extend_from_slice(payload);
+        }
+        data.extend_from_slice(&[0xFF, 0xD9]);
+        data
+    }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_png"))]
+    fn png_color_chunks() {
+        let mut chrm = Vec::new();
+        for value in [31270_u32, 32900, 64000, 33000, 30000, 60000, 15000, 6000] {
+            chrm.extend_from_slice(&value.to_be_bytes());
+        }
+
+        let data = png(&[
+            (b"IHDR", &[0; 13]),
+            (b"sRGB", &[0]),
+            (b"gAMA", &45455_u32.to_be_bytes()),
+            (b"cHRM", &chrm),
+            (b"IDAT", &[]),
+            (b"IEND", &[]),
+        ]);
+
+        let info = color_info_from_memory(&data).expect("Failed to read color info");
+        assert_eq!(info.icc_profile, None);
+        assert_eq!(info.srgb, Some(RenderingIntent::Perceptual));
+        assert_eq!(info.gamma, Some(0.45455));
+
+        let chromaticities = info.chromaticities.expect("Missing chromaticities");
+        assert_eq!(chromaticities.white, (0.3127, 0.329));
+        assert_eq!(chromaticities.red, (0.64, 0.33));
+        assert_eq!(chromaticities.green, (0.3, 0.6));
+        assert_eq!(chromaticities.blue, (0.15, 0.06));
+
+        // Cut in the middle of sRGB chunk
+        let truncated = &data[..40];
+        assert!(matches!(
+            color_info_from_memory(truncated),
+            Err(Error::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_png"))]
+    fn png_icc_profile() {
+        let mut iccp = b"profile\0\0".to_vec();
+        // zlib stream of "icc profile data"
+        iccp.extend_from_slice(&[
+            0x78, 0x9c, 0xcb, 0x4c, 0x4e, 0x56, 0x28, 0x28, 0xca, 0x4f, 0xcb, 0xcc, 0x49, 0x55,
+            0x48, 0x49, 0x2c, 0x49, 0x04, 0x00, 0x32, 0xbf, 0x05, 0xfb,
+        ]);
+
+        let data = png(&[(b"IHDR", &[0; 13]), (b"iCCP", &iccp), (b"IEND", &[])]);
+        let info = color_info_from_memory(&data).expect("Failed to read color info");
+        assert_eq!(info.icc_profile.as_deref(), Some(&b"icc profile data"[..]));
+        assert_eq!(info.srgb, None);
+    }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_png"))]
+    fn png_icc_profile_bomb() {
+        let mut iccp = b"profile\0\0".to_vec();
+        iccp.extend_from_slice(&zlib_runs(0, MAX_ICC_PROFILE_LEN / 258 + 1));
+
+        let data = png(&[(b"IHDR", &[0; 13]), (b"iCCP", &iccp), (b"IEND", &[])]);
+        assert!(matches!(
+            color_info_from_memory(&data),
+            Err(Error::LimitsExceeded)
+        ));
+    }
+
+    #[test]
+    #[cfg(not(feature = "stbi_no_jpeg"))]
+    fn jpeg_icc_profile() {
+        let data = jpeg(&[
+            (0xE0, b"JFIF\0"),
+            (0xE2, b"ICC_PROFILE\0\x02\x02 data"),
+            (0xE2, b"ICC_PROFILE\0\x01\x02icc profile"),
+            (0xDA, &[]),
+        ]);
+
+        let info = color_info_from_memory(&data).expect("Failed to read color info");
+        assert_eq!(info.icc_profile.as_deref(), Some(&b"icc profile data"[..]));
+
+        let missing_part = jpeg(&[(0xE2, b"ICC_PROFILE\0\x01\x02icc profile")]);
+        assert!(matches!(
+            color_info_from_memory(&missing_part),
+            Err(Error::Corrupt(_))
+        ));
+
+        let none = jpeg(&[(0xE0, b"JFIF\0")]);
+        assert_eq!(
+            color_info_from_memory(&none).expect("Failed to read color info"),
+            ColorInfo::default()
+        );
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(matches!(
+            color_info_from_memory(b"not an image"),
+            Err(Error::UnknownFormat)
+        ));
+    }
+}
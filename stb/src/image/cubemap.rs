@@ -0,0 +1,510 @@
+//! Conversion between equirectangular panoramas and cubemaps, e.g. for HDR skies loaded with
+//! `stbi_loadf_from_memory`.
+//!
+//! Faces follow the OpenGL cubemap convention: looking at a face from the center of the cube,
+//! +Y is up on the side faces, +Z is the face below +Y and -Z the face above it.
+//! The center of an equirectangular image looks at +Z, its left and right edges at -Z, +X is
+//! a quarter of the width to the right of the center and +Y is the top row.
+//!
+//! Samples are interpolated bilinearly, which keeps outputs smooth as long as their resolution
+//! is close to the resolution of the source. Buffers hold rows of `channels` floats per pixel
+//! without padding, so they can be written with `stbi_write_hdr` directly.
+
+use super::ImageView;
+use std::f32::consts::PI;
+
+/// Cube face, in the order of OpenGL and Direct3D face indices
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Face {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl Face {
+    /// All faces in index order
+    pub const ALL: [Face; 6] = [
+        Face::PositiveX,
+        Face::NegativeX,
+        Face::PositiveY,
+        Face::NegativeY,
+        Face::PositiveZ,
+        Face::NegativeZ,
+    ];
+
+    /// Direction through the face at `s`, `t` in `[-1, 1]`, `s` grows to the right and `t`
+    /// grows downwards
+    fn direction(self, s: f32, t: f32) -> [f32; 3] {
+        match self {
+            Face::PositiveX => [1.0, -t, -s],
+            Face::NegativeX => [-1.0, -t, s],
+            Face::PositiveY => [s, 1.0, t],
+            Face::NegativeY => [s, -1.0, -t],
+            Face::PositiveZ => [s, -t, 1.0],
+            Face::NegativeZ => [-s, -t, -1.0],
+        }
+    }
+
+    /// Face `direction` points through, along with `s`, `t` in `[-1, 1]`
+    fn project([x, y, z]: [f32; 3]) -> (Face, f32, f32) {
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+
+        if ax >= ay && ax >= az {
+            if x > 0.0 {
+                (Face::PositiveX, -z / ax, -y / ax)
+            } else {
+                (Face::NegativeX, z / ax, -y / ax)
+            }
+        } else if ay >= az {
+            if y > 0.0 {
+                (Face::PositiveY, x / ay, z / ay)
+            } else {
+                (Face::NegativeY, x / ay, -z / ay)
+            }
+        } else if z > 0.0 {
+            (Face::PositiveZ, x / az, -y / az)
+        } else {
+            (Face::NegativeZ, -x / az, -y / az)
+        }
+    }
+}
+
+/// Arrangement of faces in a single cross shaped image, empty cells are zero
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CrossLayout {
+    /// 4 x 3 faces: -X, +Z, +X and -Z in the middle row, +Y above and -Y below +Z
+    Horizontal,
+    /// 3 x 4 faces: -X, +Z and +X in the second row, +Y above +Z, -Y and -Z (rotated by 180
+    /// degrees) below it
+    Vertical,
+}
+
+impl CrossLayout {
+    /// Size of the layout in faces
+    fn cells(self) -> (usize, usize) {
+        match self {
+            CrossLayout::Horizontal => (4, 3),
+            CrossLayout::Vertical => (3, 4),
+        }
+    }
+
+    /// Cell of `face` and whether the face is rotated by 180 degrees
+    fn cell(self, face: Face) -> (usize, usize, bool) {
+        match (self, face) {
+            (_, Face::PositiveY) => (1, 0, false),
+            (_, Face::NegativeX) => (0, 1, false),
+            (_, Face::PositiveZ) => (1, 1, false),
+            (_, Face::PositiveX) => (2, 1, false),
+            (_, Face::NegativeY) => (1, 2, false),
+            (CrossLayout::Horizontal, Face::NegativeZ) => (3, 1, false),
+            (CrossLayout::Vertical, Face::NegativeZ) => (1, 3, true),
+        }
+    }
+}
+
+/// Six square faces of the same size
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cubemap {
+    size: usize,
+    channels: usize,
+    faces: [Vec<f32>; 6],
+}
+
+impl Cubemap {
+    /// Creates a cubemap of black `size` x `size` faces
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is zero.
+    pub fn new(size: usize, channels: usize) -> Self {
+        assert!(channels > 0, "Cubemap must have at least one channel");
+
+        let face = vec![0.0; size * size * channels];
+        Cubemap {
+            size,
+            channels,
+            faces: [
+                face.clone(),
+                face.clone(),
+                face.clone(),
+                face.clone(),
+                face.clone(),
+                face,
+            ],
+        }
+    }
+
+    /// Creates a cubemap from `faces` in [`Face::ALL`] order. Returns `None` unless all faces
+    /// are squares of the same size and the same non-zero number of channels.
+    pub fn from_faces(faces: [ImageView<'_, f32>; 6]) -> Option<Self> {
+        let (size, channels) = (faces[0].width(), faces[0].channels());
+        if channels == 0
+            || faces.iter().any(|face| {
+                face.width() != size || face.height() != size || face.channels() != channels
+            })
+        {
+            return None;
+        }
+
+        let mut cubemap = Cubemap::new(size, channels);
+        for (dest, face) in cubemap.faces.iter_mut().zip(&faces) {
+            copy_rows(*face, dest, size * channels, false);
+        }
+
+        Some(cubemap)
+    }
+
+    /// Projects `equirect` panorama onto faces of `size` x `size` pixels.
+    /// Returns `None` if `equirect` has no channels.
+    pub fn from_equirect(equirect: ImageView<'_, f32>, size: usize) -> Option<Self> {
+        let channels = equirect.channels();
+        if channels == 0 {
+            return None;
+        }
+
+        let (width, height) = (equirect.width() as f32, equirect.height() as f32);
+        let mut cubemap = Cubemap::new(size, channels);
+
+        for (face, dest) in Face::ALL.iter().zip(&mut cubemap.faces) {
+            for (i, pixel) in dest.chunks_exact_mut(channels).enumerate() {
+                let [x, y, z] = face.direction(coord(i % size, size), coord(i / size, size));
+
+                // Longitude grows from -Z through +X, latitude from +Y down
+                let u = 0.5 + x.atan2(z) / (2.0 * PI);
+                let v = (y / (x * x + y * y + z * z).sqrt()).acos() / PI;
+                sample(equirect, u * width, v * height, true, pixel);
+            }
+        }
+
+        Some(cubemap)
+    }
+
+    /// Unwraps faces from `cross` image in `layout`. Returns `None` unless `cross` dimensions
+    /// are multiples of the layout size in faces with square faces, or if it has no channels.
+    pub fn from_cross(cross: ImageView<'_, f32>, layout: CrossLayout) -> Option<Self> {
+        let (columns, rows) = layout.cells();
+        let size = cross.width() / columns;
+        if size * columns != cross.width() || size * rows != cross.height() || cross.channels() == 0
+        {
+            return None;
+        }
+
+        let mut cubemap = Cubemap::new(size, cross.channels());
+        for (face, dest) in Face::ALL.iter().zip(&mut cubemap.faces) {
+            let (column, row, rotated) = layout.cell(*face);
+            let cell = cross.crop(column * size, row * size, size, size)?;
+            copy_rows(cell, dest, size * cubemap.channels, rotated);
+        }
+
+        Some(cubemap)
+    }
+
+    /// Face width and height in pixels
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of channels per pixel
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Returns pixels of `face`
+    pub fn face(&self, face: Face) -> &[f32] {
+        &self.faces[face as usize]
+    }
+
+    /// Returns mutable pixels of `face`
+    pub fn face_mut(&mut self, face: Face) -> &mut [f32] {
+        &mut self.faces[face as usize]
+    }
+
+    /// Returns a view of `face`
+    pub fn view(&self, face: Face) -> ImageView<'_, f32> {
+        let (size, channels) = (self.size, self.channels);
+        ImageView::new(self.face(face), size, size, channels, size * channels)
+            .expect("Face must hold size x size pixels")
+    }
+
+    /// Consumes this cubemap into faces in [`Face::ALL`] order
+    pub fn into_faces(self) -> [Vec<f32>; 6] {
+        self.faces
+    }
+
+    /// Projects faces onto a `width` x `height` equirectangular panorama.
+    /// Samples are interpolated within faces, clamping at face edges.
+    pub fn to_equirect(&self, width: usize, height: usize) -> Vec<f32> {
+        let channels = self.channels;
+        let mut equirect = vec![0.0; width * height * channels];
+
+        for (i, pixel) in equirect.chunks_exact_mut(channels).enumerate() {
+            let longitude = ((i % width) as f32 + 0.5) / width as f32 * 2.0 * PI - PI;
+            let latitude = ((i / width) as f32 + 0.5) / height as f32 * PI;
+
+            let direction = [
+                latitude.sin() * longitude.sin(),
+                latitude.cos(),
+                latitude.sin() * longitude.cos(),
+            ];
+            let (face, s, t) = Face::project(direction);
+
+            let size = self.size as f32;
+            let (x, y) = ((s + 1.0) / 2.0 * size, (t + 1.0) / 2.0 * size);
+            sample(self.view(face), x, y, false, pixel);
+        }
+
+        equirect
+    }
+
+    /// Arranges faces into a single image in `layout`, returns pixels along with image width
+    /// and height
+    pub fn to_cross(&self, layout: CrossLayout) -> (Vec<f32>, usize, usize) {
+        let (columns, rows) = layout.cells();
+        let (width, height) = (columns * self.size, rows * self.size);
+        let mut cross = vec![0.0; width * height * self.channels];
+
+        for face in Face::ALL.iter() {
+            let (column, row, rotated) = layout.cell(*face);
+            let start = (row * self.size * width + column * self.size) * self.channels;
+            copy_rows(
+                self.view(*face),
+                &mut cross[start..],
+                width * self.channels,
+                rotated,
+            );
+        }
+
+        (cross, width, height)
+    }
+}
+
+/// Center of pixel `index` out of `size` in `[-1, 1]`
+fn coord(index: usize, size: usize) -> f32 {
+    (index as f32 + 0.5) / size as f32 * 2.0 - 1.0
+}
+
+/// Copies rows of `view` into rows of `dest` starting every `stride` samples, rotating them
+/// by 180 degrees if `rotated`
+fn copy_rows(view: ImageView<'_, f32>, dest: &mut [f32], stride: usize, rotated: bool) {
+    let row_size = view.width() * view.channels();
+
+    for y in 0..view.height() {
+        let dest = &mut dest[y * stride..][..row_size];
+        if rotated {
+            let source = view.row(view.height() - 1 - y).unwrap_or_default();
+            copy_reversed(source, dest, view.channels());
+        } else {
+            dest.copy_from_slice(view.row(y).unwrap_or_default());
+        }
+    }
+}
+
+/// Copies pixels of `source` row into `dest` in reverse order
+fn copy_reversed(source: &[f32], dest: &mut [f32], channels: usize) {
+    for (dest, source) in dest
+        .chunks_exact_mut(channels)
+        .zip(source.chunks_exact(channels).rev())
+    {
+        dest.copy_from_slice(source);
+    }
+}
+
+/// Interpolates pixels of `view` around `x`, `y` (in pixels, pixel centers are at .5) into
+/// `dest`. Columns wrap around if `wrap`, otherwise they're clamped like rows.
+fn sample(view: ImageView<'_, f32>, x: f32, y: f32, wrap: bool, dest: &mut [f32]) {
+    let (width, height) = (view.width() as isize, view.height() as isize);
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (left, top) = (x.floor(), y.floor());
+    let (tx, ty) = (x - left, y - top);
+
+    let column = |x: isize| {
+        if wrap {
+            x.rem_euclid(width) as usize
+        } else {
+            x.clamp(0, width - 1) as usize
+        }
+    };
+    let row = |y: isize| y.clamp(0, height - 1) as usize;
+
+    let (left, top) = (left as isize, top as isize);
+    let (x0, x1, y0, y1) = (column(left), column(left + 1), row(top), row(top + 1));
+    let pixel = |x: usize, y: usize| {
+        let start = y * view.stride() + x * view.channels();
+        &view.as_slice()[start..start + view.channels()]
+    };
+
+    let (p00, p10, p01, p11) = (pixel(x0, y0), pixel(x1, y0), pixel(x0, y1), pixel(x1, y1));
+    for (c, dest) in dest.iter_mut().enumerate() {
+        let top = p00[c] + (p10[c] - p00[c]) * tx;
+        let bottom = p01[c] + (p11[c] - p01[c]) * tx;
+        *dest = top + (bottom - top) * ty;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Equirect panorama with the direction of each pixel center as its RGB color
+    fn direction_equirect(width: usize, height: usize) -> Vec<f32> {
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let longitude = (x as f32 + 0.5) / width as f32 * 2.0 * PI - PI;
+                let latitude = (y as f32 + 0.5) / height as f32 * PI;
+                pixels.extend_from_slice(&[
+                    latitude.sin() * longitude.sin(),
+                    latitude.cos(),
+                    latitude.sin() * longitude.cos(),
+                ]);
+            }
+        }
+        pixels
+    }
+
+    fn normalized([x, y, z]: [f32; 3]) -> [f32; 3] {
+        let length = (x * x + y * y + z * z).sqrt();
+        [x / length, y / length, z / length]
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32], tolerance: f32) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() <= tolerance,
+                "{:?} is not within {} of {:?}",
+                actual,
+                tolerance,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn project_face_directions() {
+        for face in Face::ALL.iter() {
+            for (s, t) in [(0.0, 0.0), (-0.5, 0.25), (0.75, -0.9)] {
+                let (projected, ps, pt) = Face::project(face.direction(s, t));
+                assert_eq!(projected, *face);
+                assert_close(&[ps, pt], &[s, t], 1e-6);
+            }
+        }
+
+        // Neighbouring faces share edges
+        let (face, s, _) = Face::project(Face::PositiveZ.direction(1.0, 0.0));
+        assert!(face == Face::PositiveZ || (face == Face::PositiveX && s == -1.0));
+    }
+
+    #[test]
+    fn equirect_to_cubemap() {
+        let (width, height) = (256, 128);
+        let pixels = direction_equirect(width, height);
+        let equirect = ImageView::new(&pixels, width, height, 3, width * 3).unwrap();
+
+        let cubemap = Cubemap::from_equirect(equirect, 16).unwrap();
+        assert_eq!(cubemap.size(), 16);
+        assert_eq!(cubemap.channels(), 3);
+
+        for face in Face::ALL.iter() {
+            let view = cubemap.view(*face);
+            for (y, row) in view.rows().enumerate() {
+                for (x, pixel) in row.chunks_exact(3).enumerate() {
+                    let direction = normalized(face.direction(coord(x, 16), coord(y, 16)));
+                    assert_close(pixel, &direction, 0.02);
+                }
+            }
+        }
+
+        // Panorama center looks at +Z, +Y is up
+        let center = cubemap.view(Face::PositiveZ).get_pixel(8, 8).unwrap();
+        assert!(center[2] > 0.99);
+        let up = cubemap.view(Face::PositiveY).get_pixel(8, 8).unwrap();
+        assert!(up[1] > 0.99);
+    }
+
+    #[test]
+    fn cubemap_to_equirect() {
+        let size = 32;
+        let mut cubemap = Cubemap::new(size, 3);
+        for face in Face::ALL.iter() {
+            for (i, pixel) in cubemap.face_mut(*face).chunks_exact_mut(3).enumerate() {
+                let direction = face.direction(coord(i % size, size), coord(i / size, size));
+                pixel.copy_from_slice(&normalized(direction));
+            }
+        }
+
+        let (width, height) = (128, 64);
+        let equirect = cubemap.to_equirect(width, height);
+        assert_eq!(equirect.len(), width * height * 3);
+        assert_close(&equirect, &direction_equirect(width, height), 0.05);
+    }
+
+    #[test]
+    fn cross_layouts() {
+        let size = 4;
+        let mut cubemap = Cubemap::new(size, 4);
+        for (index, face) in Face::ALL.iter().enumerate() {
+            for (i, sample) in cubemap.face_mut(*face).iter_mut().enumerate() {
+                *sample = (index * 1000 + i) as f32;
+            }
+        }
+
+        for layout in [CrossLayout::Horizontal, CrossLayout::Vertical] {
+            let (pixels, width, height) = cubemap.to_cross(layout);
+            let (columns, rows) = layout.cells();
+            assert_eq!((width, height), (columns * size, rows * size));
+
+            let cross = ImageView::new(&pixels, width, height, 4, width * 4).unwrap();
+            assert_eq!(Cubemap::from_cross(cross, layout).as_ref(), Some(&cubemap));
+
+            // +Z is the second face of the second row in both layouts
+            assert_eq!(
+                cross.get_pixel(size, size),
+                cubemap.view(Face::PositiveZ).get_pixel(0, 0)
+            );
+        }
+
+        // -Z is upside down in the vertical cross
+        let (pixels, width, height) = cubemap.to_cross(CrossLayout::Vertical);
+        let cross = ImageView::new(&pixels, width, height, 4, width * 4).unwrap();
+        let negative_z = cubemap.view(Face::NegativeZ);
+        assert_eq!(
+            cross.get_pixel(size, 4 * size - 1),
+            negative_z.get_pixel(size - 1, 0)
+        );
+
+        let (pixels, width, height) = cubemap.to_cross(CrossLayout::Horizontal);
+        let cross = ImageView::new(&pixels, width - 1, height, 4, width * 4).unwrap();
+        assert_eq!(Cubemap::from_cross(cross, CrossLayout::Horizontal), None);
+    }
+
+    #[test]
+    fn cubemap_from_faces() {
+        let cubemap = Cubemap::new(2, 3);
+        let faces = Face::ALL.map(|face| cubemap.view(face));
+        assert_eq!(Cubemap::from_faces(faces).as_ref(), Some(&cubemap));
+
+        let small = Cubemap::new(1, 3);
+        let mut faces = Face::ALL.map(|face| cubemap.view(face));
+        faces[3] = small.view(Face::NegativeY);
+        assert_eq!(Cubemap::from_faces(faces), None);
+    }
+
+    #[test]
+    fn reject_no_channels() {
+        let view = ImageView::new(&[], 4, 3, 0, 0).unwrap();
+        assert_eq!(Cubemap::from_equirect(view, 4), None);
+        assert_eq!(Cubemap::from_cross(view, CrossLayout::Horizontal), None);
+        let face = ImageView::new(&[], 2, 2, 0, 0).unwrap();
+        assert_eq!(Cubemap::from_faces([face; 6]), None);
+
+        let result = std::panic::catch_unwind(|| Cubemap::new(4, 0));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! EXIF orientation of JPEG images.
+//! stb ignores EXIF metadata, so the APP1 segment is parsed here.
+
+use super::chunks::{self, JPEG_APP1};
+use std::io::Read;
+
+/// How the stored image must be transformed to be displayed upright, as described by the EXIF
+/// Orientation tag (values 1-8)
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Orientation {
+    /// Image is stored upright (1)
+    #[default]
+    Normal = 1,
+    /// Mirrored left to right (2)
+    FlipHorizontal = 2,
+    /// Rotated by 180 degrees (3)
+    Rotate180 = 3,
+    /// Mirrored top to bottom (4)
+    FlipVertical = 4,
+    /// Mirrored along the top left to bottom right diagonal (5)
+    Transpose = 5,
+    /// Needs to be rotated by 90 degrees clockwise (6)
+    Rotate90 = 6,
+    /// Mirrored along the top right to bottom left diagonal (7)
+    Transverse = 7,
+    /// Needs to be rotated by 90 degrees counter clockwise (8)
+    Rotate270 = 8,
+}
+
+impl Orientation {
+    /// Returns orientation for the EXIF tag `value`, or `None` if it's out of 1-8 range
+    pub fn from_exif(value: u16) -> Option<Self> {
+        let orientation = match value {
+            1 => Orientation::Normal,
+            2 => Orientation::FlipHorizontal,
+            3 => Orientation::Rotate180,
+            4 => Orientation::FlipVertical,
+            5 => Orientation::Transpose,
+            6 => Orientation::Rotate90,
+            7 => Orientation::Transverse,
+            8 => Orientation::Rotate270,
+            _ => return None,
+        };
+
+        Some(orientation)
+    }
+
+    /// Returns EXIF tag value (1-8)
+    pub fn to_exif(self) -> u16 {
+        self as u16
+    }
+
+    /// Returns `true` if width and height are swapped once the orientation is applied
+    pub fn swaps_dimensions(self) -> bool {
+        matches!(
+            self,
+            Orientation::Transpose
+                | Orientation::Rotate90
+                | Orientation::Transverse
+                | Orientation::Rotate270
+        )
+    }
+
+    /// Maps pixel `x`, `y` of the upright image to the pixel of the `width` x `height` stored one
+    pub(super) fn source(self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Orientation::Normal => (x, y),
+            Orientation::FlipHorizontal => (width - 1 - x, y),
+            Orientation::Rotate180 => (width - 1 - x, height - 1 - y),
+            Orientation::FlipVertical => (x, height - 1 - y),
+            Orientation::Transpose => (y, x),
+            Orientation::Rotate90 => (y, height - 1 - x),
+            Orientation::Transverse => (width - 1 - y, height - 1 - x),
+            Orientation::Rotate270 => (width - 1 - y, x),
+        }
+    }
+}
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const ORIENTATION_TAG: u16 = 0x0112;
+const SHORT_TYPE: u16 = 3;
+
+/// Returns orientation stored in EXIF metadata of the JPEG image in `buffer`.
+/// Returns `None` if the buffer isn't a JPEG image or it has no valid orientation tag.
+pub fn jpeg_orientation(buffer: &[u8]) -> Option<Orientation> {
+    read_orientation(&mut &buffer[..])
+}
+
+/// Reads JPEG segments from `reader` up to the first APP1 EXIF segment or the scan data
+pub(super) fn read_orientation<R: Read>(reader: &mut R) -> Option<Orientation> {
+    for segment in chunks::jpeg_segments(reader)? {
+        let (marker, payload) = segment.ok()?;
+        if marker != JPEG_APP1 {
+            continue;
+        }
+
+        if let Some(tiff) = payload.strip_prefix(EXIF_HEADER) {
+            return tiff_orientation(tiff);
+        }
+    }
+
+    None
+}
+
+/// Looks up the orientation tag in the first IFD of TIFF structure in `data`
+fn tiff_orientation(data: &[u8]) -> Option<Orientation> {
+    let big_endian = match data.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+
+    let u16_at = |offset: usize| {
+        let bytes = [*data.get(offset)?, *data.get(offset + 1)?];
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+
+    let u32_at = |offset: usize| {
+        let (high, low) = (u16_at(offset)?, u16_at(offset + 2)?);
+        Some(if big_endian {
+            (high as u32) << 16 | low as u32
+        } else {
+            (low as u32) << 16 | high as u32
+        })
+    };
+
+    if u16_at(2)? != 42 {
+        return None;
+    }
+
+    let ifd = u32_at(4)? as usize;
+    let count = u16_at(ifd)? as usize;
+
+    (0..count)
+        .map(|index| ifd + 2 + index * 12)
+        .find(|entry| u16_at(*entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| {
+            if u16_at(entry + 2)? != SHORT_TYPE {
+                return None;
+            }
+            Orientation::from_exif(u16_at(entry + 8)?)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::chunks::{JPEG_EOI, JPEG_SOI};
+
+    fn jpeg(tiff: &[u8]) -> Vec<u8> {
+        let mut data = vec![0xFF, JPEG_SOI];
+
+        // APP0 segment before EXIF one
+        data.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x06, b'J', b'F', b'I', b'F']);
+
+        let len = (2 + EXIF_HEADER.len() + tiff.len()) as u16;
+        data.extend_from_slice(&[0xFF, JPEG_APP1]);
+        data.extend_from_slice(&len.to_be_bytes());
+        data.extend_from_slice(EXIF_HEADER);
+        data.extend_from_slice(tiff);
+
+        data.extend_from_slice(&[0xFF, JPEG_EOI]);
+        data
+    }
+
+    #[test]
+    fn parse_orientation() {
+        let big_endian = [
+            b'M', b'M', 0, 42, 0, 0, 0, 8, // Header
+            0, 2, // Entries
+            0x01, 0x0F, 0, 2, 0, 0, 0, 1, 0, 0, 0, 0, // Make
+            0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, // Orientation
+        ];
+        assert_eq!(
+            jpeg_orientation(&jpeg(&big_endian)),
+            Some(Orientation::Rotate90)
+        );
+
+        let little_endian = [
+            b'I', b'I', 42, 0, 8, 0, 0, 0, // Header
+            1, 0, // Entries
+            0x12, 0x01, 3, 0, 1, 0, 0, 0, 8, 0, 0, 0, // Orientation
+        ];
+        assert_eq!(
+            jpeg_orientation(&jpeg(&little_endian)),
+            Some(Orientation::Rotate270)
+        );
+    }
+
+    #[test]
+    fn parse_invalid_orientation() {
+        let out_of_range = [
+            b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, 0x12, 0x01, 3, 0, 1, 0, 0, 0, 9, 0, 0, 0,
+        ];
+        assert_eq!(jpeg_orientation(&jpeg(&out_of_range)), None);
+
+        let truncated = [b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, 0x12, 0x01];
+        assert_eq!(jpeg_orientation(&jpeg(&truncated)), None);
+
+        assert_eq!(jpeg_orientation(&jpeg(b"")), None);
+        assert_eq!(jpeg_orientation(b"\x89PNG\r\n\x1a\n"), None);
+        assert_eq!(jpeg_orientation(&[]), None);
+    }
+
+    #[test]
+    fn source_pixel() {
+        // 3 x 2 image, upright one is 2 x 3 when dimensions are swapped
+        assert_eq!(Orientation::Normal.source(2, 1, 3, 2), (2, 1));
+        assert_eq!(Orientation::FlipHorizontal.source(0, 0, 3, 2), (2, 0));
+        assert_eq!(Orientation::Rotate180.source(0, 0, 3, 2), (2, 1));
+        assert_eq!(Orientation::FlipVertical.source(0, 0, 3, 2), (0, 1));
+        assert_eq!(Orientation::Transpose.source(1, 2, 3, 2), (2, 1));
+        assert_eq!(Orientation::Rotate90.source(0, 0, 3, 2), (0, 1));
+        assert_eq!(Orientation::Transverse.source(0, 0, 3, 2), (2, 1));
+        assert_eq!(Orientation::Rotate270.source(0, 0, 3, 2), (2, 0));
+    }
+}
@@ -0,0 +1,236 @@
+//! Image format detection by file signature, without decoding.
+
+use std::io::{self, Read};
+
+/// Number of leading bytes required to detect any supported format
+const HEADER_SIZE: usize = 92;
+
+/// Image formats stb is able to decode
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Bmp,
+    Tga,
+    Psd,
+    Gif,
+    Hdr,
+    Pic,
+    Pnm,
+}
+
+impl ImageFormat {
+    /// Returns `false` if decoding of this format is disabled with `stbi_no_*` feature
+    pub fn is_enabled(self) -> bool {
+        match self {
+            ImageFormat::Png => cfg!(not(feature = "stbi_no_png")),
+            ImageFormat::Jpeg => cfg!(not(feature = "stbi_no_jpeg")),
+            ImageFormat::Bmp => cfg!(not(feature = "stbi_no_bmp")),
+            ImageFormat::Tga => cfg!(not(feature = "stbi_no_tga")),
+            ImageFormat::Psd => cfg!(not(feature = "stbi_no_psd")),
+            ImageFormat::Gif => cfg!(not(feature = "stbi_no_gif")),
+            ImageFormat::Hdr => cfg!(not(feature = "stbi_no_hdr")),
+            ImageFormat::Pic => cfg!(not(feature = "stbi_no_pic")),
+            ImageFormat::Pnm => cfg!(not(feature = "stbi_no_pnm")),
+        }
+    }
+}
+
+/// Detects image format of `buffer` by its signature.
+///
+/// Formats are recognized regardless of `stbi_no_*` features, use [`ImageFormat::is_enabled`]
+/// to check whether the format can actually be decoded.
+pub fn detect_format(buffer: &[u8]) -> Option<ImageFormat> {
+    // Same order stb probes formats in, TGA has no signature so it goes last
+    if is_jpeg(buffer) {
+        Some(ImageFormat::Jpeg)
+    } else if buffer.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(ImageFormat::Png)
+    } else if is_bmp(buffer) {
+        Some(ImageFormat::Bmp)
+    } else if buffer.starts_with(b"GIF87a") || buffer.starts_with(b"GIF89a") {
+        Some(ImageFormat::Gif)
+    } else if buffer.starts_with(b"8BPS\x00\x01") {
+        Some(ImageFormat::Psd)
+    } else if is_pic(buffer) {
+        Some(ImageFormat::Pic)
+    } else if buffer.starts_with(b"P5") || buffer.starts_with(b"P6") {
+        Some(ImageFormat::Pnm)
+    } else if buffer.starts_with(b"#?RADIANCE\n") || buffer.starts_with(b"#?RGBE\n") {
+        Some(ImageFormat::Hdr)
+    } else if is_tga(buffer) {
+        Some(ImageFormat::Tga)
+    } else {
+        None
+    }
+}
+
+/// Reader returned by [`detect_format_from_reader`], yields the consumed header bytes followed
+/// by the rest of the wrapped reader
+pub type HeaderReader<R> = io::Chain<io::Cursor<Vec<u8>>, R>;
+
+/// Detects image format of the data from `reader` by its signature.
+///
+/// A few leading bytes are consumed from `reader`, so it's returned chained after them along
+/// with the format, and reads the whole stream from the initial position. Pass `&mut reader`
+/// to keep the ownership.
+pub fn detect_format_from_reader<R>(
+    mut reader: R,
+) -> io::Result<(Option<ImageFormat>, HeaderReader<R>)>
+where
+    R: io::Read,
+{
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    reader
+        .by_ref()
+        .take(HEADER_SIZE as u64)
+        .read_to_end(&mut header)?;
+
+    let format = detect_format(&header);
+    Ok((format, io::Cursor::new(header).chain(reader)))
+}
+
+fn is_jpeg(buffer: &[u8]) -> bool {
+    // SOI marker followed by any other marker
+    buffer.len() >= 3 && buffer[0] == 0xFF && buffer[1] == 0xD8 && buffer[2] == 0xFF
+}
+
+fn is_bmp(buffer: &[u8]) -> bool {
+    if !buffer.starts_with(b"BM") || buffer.len() < 18 {
+        return false;
+    }
+
+    let header_size = u32_le(&buffer[14..18]);
+    [12, 40, 56, 108, 124].contains(&header_size)
+}
+
+fn is_pic(buffer: &[u8]) -> bool {
+    buffer.starts_with(b"\x53\x80\xF6\x34") && buffer.len() >= 92 && &buffer[88..92] == b"PICT"
+}
+
+/// TGA has no signature, so apply the same header sanity checks stb does
+fn is_tga(buffer: &[u8]) -> bool {
+    if buffer.len() < 18 {
+        return false;
+    }
+
+    let color_map_type = buffer[1];
+    let image_type = buffer[2];
+
+    match color_map_type {
+        0 => {
+            if ![2, 3, 10, 11].contains(&image_type) {
+                return false;
+            }
+        }
+        1 => {
+            if image_type != 1 && image_type != 9 {
+                return false;
+            }
+            // Color map entry size
+            if ![8, 15, 16, 24, 32].contains(&buffer[7]) {
+                return false;
+            }
+        }
+        _ => return false,
+    }
+
+    let width = u16_le(&buffer[12..14]);
+    let height = u16_le(&buffer[14..16]);
+    if width < 1 || height < 1 {
+        return false;
+    }
+
+    let bits_per_pixel = buffer[16];
+    if color_map_type == 1 {
+        // Color mapped images use indices
+        bits_per_pixel == 8 || bits_per_pixel == 16
+    } else {
+        [8, 15, 16, 24, 32].contains(&bits_per_pixel)
+    }
+}
+
+fn u16_le(bytes: &[u8]) -> u16 {
+    u16::from(bytes[0]) | u16::from(bytes[1]) << 8
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from(u16_le(&bytes[0..2])) | u32::from(u16_le(&bytes[2..4])) << 16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::test_util::fixture;
+
+    #[test]
+    fn detect_fixtures() {
+        assert_eq!(detect_format(&fixture("white.png")), Some(ImageFormat::Png));
+        assert_eq!(
+            detect_format(&fixture("animated.gif")),
+            Some(ImageFormat::Gif)
+        );
+    }
+
+    #[test]
+    fn detect_signatures() {
+        let mut bmp = b"BM".to_vec();
+        bmp.resize(14, 0);
+        bmp.extend_from_slice(&[40, 0, 0, 0]);
+
+        let mut pic = b"\x53\x80\xF6\x34".to_vec();
+        pic.resize(88, 0);
+        pic.extend_from_slice(b"PICT");
+
+        let mut tga = vec![0; 18];
+        tga[2] = 2; // Uncompressed true color
+        tga[12] = 1; // Width
+        tga[14] = 1; // Height
+        tga[16] = 24; // Bits per pixel
+
+        assert_eq!(
+            detect_format(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some(ImageFormat::Jpeg)
+        );
+        assert_eq!(detect_format(&bmp), Some(ImageFormat::Bmp));
+        assert_eq!(detect_format(b"8BPS\x00\x01"), Some(ImageFormat::Psd));
+        assert_eq!(detect_format(&pic), Some(ImageFormat::Pic));
+        assert_eq!(detect_format(b"P6\n1 1\n255\n"), Some(ImageFormat::Pnm));
+        assert_eq!(detect_format(b"#?RADIANCE\n"), Some(ImageFormat::Hdr));
+        assert_eq!(detect_format(&tga), Some(ImageFormat::Tga));
+    }
+
+    #[test]
+    fn detect_unknown() {
+        assert_eq!(detect_format(&[]), None);
+        assert_eq!(detect_format(&[0; 32]), None);
+        assert_eq!(detect_format(b"BM"), None);
+        assert_eq!(detect_format(b"P3\n1 1\n255\n"), None);
+    }
+
+    #[test]
+    fn detect_from_reader() {
+        let data = fixture("white.png");
+
+        let (format, mut reader) =
+            detect_format_from_reader(&data[..]).expect("Failed to read header");
+        assert_eq!(format, Some(ImageFormat::Png));
+
+        // Header is read again, followed by the rest of the stream
+        let mut read = Vec::new();
+        reader
+            .read_to_end(&mut read)
+            .expect("Failed to read stream");
+        assert_eq!(read, data);
+
+        let (format, mut reader) =
+            detect_format_from_reader(&b"GIF89a"[..]).expect("Failed to read header");
+        assert_eq!(format, Some(ImageFormat::Gif));
+
+        let mut read = Vec::new();
+        reader
+            .read_to_end(&mut read)
+            .expect("Failed to read stream");
+        assert_eq!(read, b"GIF89a");
+    }
+}
@@ -0,0 +1,279 @@
+//! Interoperability with the `image` crate (requires `image-interop` feature).
+//!
+//! [`StbDecoder`] lets stb act as a decoder backend for `image` based code, decoded stb images
+//! convert into `image` buffers.
+
+use super::{
+    color_info_from_memory, jpeg_orientation, load_dynamic, stbi_info_from_memory,
+    stbi_is_16_bit_from_memory, Channels, DynamicImage, Error, Image, Info, LoadOptions, Result,
+    Sample,
+};
+use ::image::error::{
+    DecodingError, ImageFormatHint, LimitError, LimitErrorKind, ParameterError, ParameterErrorKind,
+    UnsupportedError, UnsupportedErrorKind,
+};
+use ::image::{ColorType, ImageBuffer, ImageDecoder, ImageError, ImageResult, Pixel};
+use std::convert::TryFrom;
+
+/// Decodes images from memory with stb, implementing `image::ImageDecoder`.
+///
+/// Images are decoded with their native precision: HDR images as 32-bit float RGB(A), 16-bit
+/// PNG and PSD images as 16-bit and everything else as 8-bit.
+pub struct StbDecoder<'a> {
+    buffer: &'a [u8],
+    options: LoadOptions,
+    info: Info,
+    color_type: ColorType,
+}
+
+impl<'a> StbDecoder<'a> {
+    /// Creates a decoder for the image in `buffer`, reading its header only
+    pub fn new(buffer: &'a [u8]) -> Result<Self> {
+        Self::with_options(buffer, LoadOptions::default())
+    }
+
+    /// Creates a decoder for the image in `buffer` which decodes it with `options`
+    pub fn with_options(buffer: &'a [u8], options: LoadOptions) -> Result<Self> {
+        let mut info = stbi_info_from_memory(buffer)?;
+
+        #[cfg(not(feature = "stbi_no_linear"))]
+        let is_hdr = super::stbi_is_hdr_from_memory(buffer);
+        #[cfg(feature = "stbi_no_linear")]
+        let is_hdr = false;
+
+        let color_type = match (is_hdr, stbi_is_16_bit_from_memory(buffer), info.components) {
+            // image has no grey float color types, so expand to RGB(A)
+            (true, _, 1) | (true, _, 3) => ColorType::Rgb32F,
+            (true, _, _) => ColorType::Rgba32F,
+            (false, true, 1) => ColorType::L16,
+            (false, true, 2) => ColorType::La16,
+            (false, true, 3) => ColorType::Rgb16,
+            (false, true, _) => ColorType::Rgba16,
+            (false, false, 1) => ColorType::L8,
+            (false, false, 2) => ColorType::La8,
+            (false, false, 3) => ColorType::Rgb8,
+            (false, false, _) => ColorType::Rgba8,
+        };
+
+        if options.apply_orientation
+            && jpeg_orientation(buffer).is_some_and(|orientation| orientation.swaps_dimensions())
+        {
+            std::mem::swap(&mut info.width, &mut info.height);
+        }
+
+        Ok(StbDecoder {
+            buffer,
+            options,
+            info,
+            color_type,
+        })
+    }
+
+    fn desired_channels(&self) -> Channels {
+        match self.color_type.channel_count() {
+            1 => Channels::Grey,
+            2 => Channels::GreyAlpha,
+            3 => Channels::Rgb,
+            _ => Channels::RgbAlpha,
+        }
+    }
+}
+
+/// Copies `samples` into `dest` as native endian bytes
+fn copy_samples<T: Copy, const N: usize>(
+    dest: &mut [u8],
+    samples: &[T],
+    to_bytes: fn(T) -> [u8; N],
+) {
+    for (dest, sample) in dest.chunks_exact_mut(N).zip(samples) {
+        dest.copy_from_slice(&to_bytes(*sample));
+    }
+}
+
+impl ImageDecoder for StbDecoder<'_> {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.info.width as u32, self.info.height as u32)
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.color_type
+    }
+
+    fn icc_profile(&mut self) -> ImageResult<Option<Vec<u8>>> {
+        Ok(color_info_from_memory(self.buffer)?.icc_profile)
+    }
+
+    fn orientation(&mut self) -> ImageResult<::image::metadata::Orientation> {
+        // Pixels are already upright if the orientation is applied by stb loaders
+        let orientation = if self.options.apply_orientation {
+            None
+        } else {
+            jpeg_orientation(self.buffer)
+        };
+
+        Ok(orientation
+            .and_then(|orientation| {
+                ::image::metadata::Orientation::from_exif(orientation.to_exif() as u8)
+            })
+            .unwrap_or(::image::metadata::Orientation::NoTransforms))
+    }
+
+    fn read_image(self, buf: &mut [u8]) -> ImageResult<()> {
+        assert_eq!(buf.len() as u64, self.total_bytes());
+
+        match load_dynamic(self.buffer, self.desired_channels(), self.options)? {
+            DynamicImage::U8(image) => buf.copy_from_slice(&image),
+            DynamicImage::U16(image) => copy_samples(buf, &image, u16::to_ne_bytes),
+            #[cfg(not(feature = "stbi_no_linear"))]
+            DynamicImage::F32(image) => copy_samples(buf, &image, f32::to_ne_bytes),
+        }
+
+        Ok(())
+    }
+
+    fn read_image_boxed(self: Box<Self>, buf: &mut [u8]) -> ImageResult<()> {
+        (*self).read_image(buf)
+    }
+}
+
+impl From<Error> for ImageError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::UnknownFormat => {
+                ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+                    ImageFormatHint::Unknown,
+                    UnsupportedErrorKind::Format(ImageFormatHint::Unknown),
+                ))
+            }
+            Error::Unsupported(reason) => {
+                ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+                    ImageFormatHint::Unknown,
+                    UnsupportedErrorKind::GenericFeature(reason.to_string()),
+                ))
+            }
+            Error::OutOfMemory => {
+                ImageError::Limits(LimitError::from_kind(LimitErrorKind::InsufficientMemory))
+            }
+            Error::TooLarge | Error::LimitsExceeded => {
+                ImageError::Limits(LimitError::from_kind(LimitErrorKind::DimensionError))
+            }
+            Error::BufferTooSmall(_) => ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::DimensionMismatch,
+            )),
+            Error::Io(err) => ImageError::IoError(err),
+            err @ Error::Corrupt(_) => {
+                ImageError::Decoding(DecodingError::new(ImageFormatHint::Unknown, err))
+            }
+        }
+    }
+}
+
+/// Converts into an image buffer if `P` has the same number of channels as the image,
+/// otherwise gives the image back
+impl<P> TryFrom<Image<P::Subpixel>> for ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    P::Subpixel: Sample,
+{
+    type Error = Image<P::Subpixel>;
+
+    fn try_from(image: Image<P::Subpixel>) -> std::result::Result<Self, Self::Error> {
+        if image.channels() != P::CHANNEL_COUNT as usize {
+            return Err(image);
+        }
+
+        let (width, height) = (image.width() as u32, image.height() as u32);
+        Ok(ImageBuffer::from_raw(width, height, image.into_vec())
+            .expect("Image size must match its dimensions"))
+    }
+}
+
+/// Converts `image` with a number of channels matching `P`
+fn into_buffer<P>(image: Image<P::Subpixel>) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    P::Subpixel: Sample,
+{
+    match ImageBuffer::try_from(image) {
+        Ok(buffer) => buffer,
+        Err(_) => unreachable!("channel count is checked by the caller"),
+    }
+}
+
+/// Picks 8-bit `image` buffer matching the number of channels
+impl From<Image<u8>> for ::image::DynamicImage {
+    fn from(image: Image<u8>) -> Self {
+        use ::image::DynamicImage as Dynamic;
+
+        match image.channels() {
+            1 => Dynamic::ImageLuma8(into_buffer(image)),
+            2 => Dynamic::ImageLumaA8(into_buffer(image)),
+            3 => Dynamic::ImageRgb8(into_buffer(image)),
+            _ => Dynamic::ImageRgba8(into_buffer(image)),
+        }
+    }
+}
+
+/// Picks 16-bit `image` buffer matching the number of channels
+impl From<Image<u16>> for ::image::DynamicImage {
+    fn from(image: Image<u16>) -> Self {
+        use ::image::DynamicImage as Dynamic;
+
+        match image.channels() {
+            1 => Dynamic::ImageLuma16(into_buffer(image)),
+            2 => Dynamic::ImageLumaA16(into_buffer(image)),
+            3 => Dynamic::ImageRgb16(into_buffer(image)),
+            _ => Dynamic::ImageRgba16(into_buffer(image)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::stbi_load_from_memory;
+    use crate::image::test_util::fixture;
+    use ::image::{Luma, Rgb};
+
+    #[test]
+    fn decode_with_image() {
+        let data = fixture("gray16.png");
+
+        let decoder = StbDecoder::new(&data).expect("Failed to create decoder");
+        assert_eq!(decoder.dimensions(), (2, 2));
+        assert_eq!(decoder.color_type(), ColorType::L16);
+
+        let image = ::image::DynamicImage::from_decoder(decoder).expect("Failed to decode image");
+        let image = image.as_luma16().expect("Expected 16-bit grey image");
+        assert_eq!(image.as_raw(), &[0, 0xFFFF, 0x1234, 0x8000]);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let err = StbDecoder::new(b"not an image")
+            .err()
+            .expect("Unknown data must fail");
+        assert!(matches!(ImageError::from(err), ImageError::Unsupported(_)));
+    }
+
+    #[test]
+    fn convert_into_image_buffer() {
+        let data = fixture("white.png");
+        let image = stbi_load_from_memory(&data, Channels::Grey, LoadOptions::default())
+            .expect("Failed to load image");
+
+        let image =
+            ImageBuffer::<Rgb<u8>, _>::try_from(image).expect_err("Channel count must not match");
+
+        let buffer = ImageBuffer::<Luma<u8>, _>::try_from(image)
+            .ok()
+            .expect("Failed to convert image");
+        assert_eq!(buffer.dimensions(), (20, 30));
+        assert!(buffer.pixels().all(|pixel| pixel.0 == [u8::MAX]));
+
+        let image = stbi_load_from_memory(&data, Channels::RgbAlpha, LoadOptions::default())
+            .expect("Failed to load image");
+        let dynamic = ::image::DynamicImage::from(image);
+        assert_eq!(dynamic.color(), ColorType::Rgba8);
+    }
+}
@@ -0,0 +1,58 @@
+//! Helpers shared by tests of the image modules.
+
+#[path = "../../tests/common/mod.rs"]
+mod common;
+
+pub use common::{fixture, fixture_path};
+
+/// Builds a PNG file out of `chunks` without checksums
+#[cfg(not(feature = "stbi_no_png"))]
+pub fn png(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (kind, chunk) in chunks {
+        data.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        data.extend_from_slice(&kind[..]);
+        data.extend_from_slice(chunk);
+        // CRC is not verified
+        data.extend_from_slice(&[0; 4]);
+    }
+    data
+}
+
+/// Builds a zlib stream of `byte` followed by `repeats` runs of 258 copies of it,
+/// which inflates to `1 + 258 * repeats` bytes at about 160:1 ratio
+#[cfg(not(feature = "stbi_no_png"))]
+pub fn zlib_runs(byte: u8, repeats: usize) -> Vec<u8> {
+    // Huffman codes are packed starting from the most significant bit
+    fn code(bits: &mut Vec<u8>, value: u16, len: u32) {
+        bits.extend((0..len).rev().map(|bit| ((value >> bit) & 1) as u8));
+    }
+
+    // Final block with fixed Huffman codes, then the literal
+    let mut bits = vec![1, 1, 0];
+    match byte {
+        0..=143 => code(&mut bits, 0x30 + u16::from(byte), 8),
+        _ => code(&mut bits, 0x190 + u16::from(byte - 144), 9),
+    }
+    for _ in 0..repeats {
+        // Length 258 and distance 1
+        code(&mut bits, 0xC5, 8);
+        code(&mut bits, 0, 5);
+    }
+    // End of block
+    code(&mut bits, 0, 7);
+
+    let mut data = vec![0x78, 0x01];
+    data.extend(
+        bits.chunks(8)
+            .map(|byte| byte.iter().rev().fold(0, |acc, bit| (acc << 1) | bit)),
+    );
+
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for _ in 0..1 + 258 * repeats {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    data.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    data
+}
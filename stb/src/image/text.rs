@@ -0,0 +1,254 @@
+//! PNG text chunks (`tEXt`, `zTXt` and `iTXt`), which stb skips while decoding.
+
+use super::chunks;
+use super::{Error, Result};
+use crate::zlib;
+
+/// Text entry stored in a PNG image
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    /// Entry keyword, e.g. `Author` or `Software`
+    pub keyword: String,
+    /// Entry text, decompressed if it was stored compressed
+    pub text: String,
+    /// Language of the text (RFC 3066 tag), only set by `iTXt` chunks
+    pub language: Option<String>,
+    /// Keyword translated to the text language, only set by `iTXt` chunks
+    pub translated_keyword: Option<String>,
+}
+
+/// Read text chunks of the PNG image in `buffer` without decoding pixels.
+/// Chunks are returned in the order they appear in the file.
+/// Compressed text of all chunks together must not decode past 8 MiB, otherwise
+/// `Error::LimitsExceeded` is returned.
+pub fn png_text_from_memory(buffer: &[u8]) -> Result<Vec<TextChunk>> {
+    let mut entries = Vec::new();
+    let mut budget = MAX_TEXT_LEN;
+
+    for chunk in chunks::png_chunks(buffer).ok_or(Error::UnknownFormat)? {
+        let (kind, data) = chunk?;
+
+        let entry = match &kind {
+            b"tEXt" => text_chunk(data),
+            b"zTXt" => compressed_text_chunk(data, &mut budget),
+            b"iTXt" => international_text_chunk(data, &mut budget),
+            _ => continue,
+        };
+
+        entries.push(entry?);
+    }
+
+    Ok(entries)
+}
+
+/// Splits `data` at the first null separator
+fn split_null(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let index = data.iter().position(|byte| *byte == 0)?;
+    Some((&data[..index], &data[index + 1..]))
+}
+
+/// `tEXt` and `zTXt` chunks are encoded with Latin-1, which maps to the first 256 code points
+fn latin1(data: &[u8]) -> String {
+    data.iter().map(|byte| char::from(*byte)).collect()
+}
+
+fn utf8(data: &[u8]) -> Result<String> {
+    String::from_utf8(data.to_vec()).map_err(|_| Error::Corrupt("bad iTXt chunk"))
+}
+
+/// Largest decoded text of all compressed chunks together, so many small chunks don't add up
+/// to a decompression bomb
+const MAX_TEXT_LEN: usize = 8 << 20;
+
+/// Inflates compressed text and converts it with `decode`, the length of the decoded text
+/// is taken from `budget`
+fn inflate(
+    data: &[u8],
+    budget: &mut usize,
+    decode: impl FnOnce(&[u8]) -> Result<String>,
+) -> Result<String> {
+    // Decoding never makes text shorter, so it can't fit if the decompressed data doesn't
+    let data = zlib::inflate_limited(data, *budget).map_err(|err| match err {
+        zlib::Error::BufferTooSmall => Error::LimitsExceeded,
+        zlib::Error::OutOfMemory => Error::OutOfMemory,
+        _ => Error::Corrupt("bad compressed text"),
+    })?;
+
+    let text = decode(&data)?;
+    *budget = budget
+        .checked_sub(text.len())
+        .ok_or(Error::LimitsExceeded)?;
+    Ok(text)
+}
+
+fn text_chunk(data: &[u8]) -> Result<TextChunk> {
+    let (keyword, text) = split_null(data).ok_or(Error::Corrupt("bad tEXt chunk"))?;
+
+    Ok(TextChunk {
+        keyword: latin1(keyword),
+        text: latin1(text),
+        ..TextChunk::default()
+    })
+}
+
+fn compressed_text_chunk(data: &[u8], budget: &mut usize) -> Result<TextChunk> {
+    match split_null(data) {
+        // Zero is the only compression method defined, which is zlib
+        Some((keyword, [0, text @ ..])) => Ok(TextChunk {
+            keyword: latin1(keyword),
+            text: inflate(text, budget, |text| Ok(latin1(text)))?,
+            ..TextChunk::default()
+        }),
+        _ => Err(Error::Corrupt("bad zTXt chunk")),
+    }
+}
+
+fn international_text_chunk(data: &[u8], budget: &mut usize) -> Result<TextChunk> {
+    let (keyword, rest) = split_null(data).ok_or(Error::Corrupt("bad iTXt chunk"))?;
+    let (compressed, rest) = match rest {
+        [0, _, rest @ ..] => (false, rest),
+        [1, 0, rest @ ..] => (true, rest),
+        _ => return Err(Error::Corrupt("bad iTXt chunk")),
+    };
+
+    let (language, rest) = split_null(rest).ok_or(Error::Corrupt("bad iTXt chunk"))?;
+    let (translated_keyword, text) = split_null(rest).ok_or(Error::Corrupt("bad iTXt chunk"))?;
+
+    let text = if compressed {
+        inflate(text, budget, utf8)?
+    } else {
+        utf8(text)?
+    };
+
+    Ok(TextChunk {
+        keyword: latin1(keyword),
+        text,
+        language: Some(utf8(language)?),
+        translated_keyword: Some(utf8(translated_keyword)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::test_util::{png, zlib_runs};
+
+    #[test]
+    fn read_text_chunks() {
+        let data = png(&[
+            (b"IHDR", &[0; 13]),
+            (b"tEXt", b"Author\0Ren\xe9"),
+            (b"IDAT", &[]),
+            (
+                b"iTXt",
+                b"Title\0\0\0uk\0\xd0\x9d\xd0\xb0\xd0\xb7\xd0\xb2\xd0\xb0\0Cat",
+            ),
+            (b"IEND", &[]),
+        ]);
+
+        let entries = png_text_from_memory(&data).expect("Failed to read text chunks");
+        assert_eq!(
+            entries,
+            vec![
+                TextChunk {
+                    keyword: "Author".to_string(),
+                    text: "René".to_string(),
+                    language: None,
+                    translated_keyword: None,
+                },
+                TextChunk {
+                    keyword: "Title".to_string(),
+                    text: "Cat".to_string(),
+                    language: Some("uk".to_string()),
+                    translated_keyword: Some("Назва".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn read_compressed_text_chunks() {
+        // zlib stream of "Built on CI"
+        let compressed = [
+            0x78, 0x9c, 0x73, 0x2a, 0xcd, 0xcc, 0x29, 0x51, 0xc8, 0xcf, 0x53, 0x70, 0xf6, 0x04,
+            0x00, 0x17, 0x82, 0x03, 0xaa,
+        ];
+
+        let mut ztxt = b"Comment\0\0".to_vec();
+        ztxt.extend_from_slice(&compressed);
+
+        let mut itxt = b"Comment\0\x01\0en\0\0".to_vec();
+        itxt.extend_from_slice(&compressed);
+
+        let data = png(&[(b"zTXt", &ztxt), (b"iTXt", &itxt), (b"IEND", &[])]);
+        let entries = png_text_from_memory(&data).expect("Failed to read text chunks");
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].text, "Built on CI");
+        assert_eq!(entries[1].text, "Built on CI");
+        assert_eq!(entries[1].language.as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn read_text_bomb() {
+        let mut ztxt = b"Comment\0\0".to_vec();
+        ztxt.extend_from_slice(&zlib_runs(0, MAX_TEXT_LEN / 258 + 1));
+
+        let data = png(&[(b"zTXt", &ztxt), (b"IEND", &[])]);
+        assert!(matches!(
+            png_text_from_memory(&data),
+            Err(Error::LimitsExceeded)
+        ));
+    }
+
+    #[test]
+    fn read_many_text_bombs() {
+        // Each chunk fits the limit, but all of them together don't
+        let bomb = zlib_runs(b'a', MAX_TEXT_LEN / 258 / 16);
+
+        let mut ztxt = b"Comment\0\0".to_vec();
+        ztxt.extend_from_slice(&bomb);
+
+        let mut itxt = b"Comment\0\x01\0\0\0".to_vec();
+        itxt.extend_from_slice(&bomb);
+
+        let mut chunks = vec![(b"zTXt", &ztxt[..]); 8];
+        chunks.extend(vec![(b"iTXt", &itxt[..]); 8]);
+        let entries = png_text_from_memory(&png(&chunks)).expect("Failed to read text chunks");
+        assert_eq!(entries.len(), 16);
+
+        chunks.push((b"zTXt", &ztxt[..]));
+        assert!(matches!(
+            png_text_from_memory(&png(&chunks)),
+            Err(Error::LimitsExceeded)
+        ));
+
+        // Latin-1 text doubles in size once converted to UTF-8
+        let mut ztxt = b"Comment\0\0".to_vec();
+        ztxt.extend_from_slice(&zlib_runs(0xE9, MAX_TEXT_LEN / 258 / 2 + 1));
+        assert!(matches!(
+            png_text_from_memory(&png(&[(b"zTXt", &ztxt)])),
+            Err(Error::LimitsExceeded)
+        ));
+    }
+
+    #[test]
+    fn read_bad_text_chunks() {
+        let data = png(&[(b"tEXt", b"no separator")]);
+        assert!(matches!(
+            png_text_from_memory(&data),
+            Err(Error::Corrupt(_))
+        ));
+
+        let data = png(&[(b"iTXt", b"Title\0\0\0\0\0\xff")]);
+        assert!(matches!(
+            png_text_from_memory(&data),
+            Err(Error::Corrupt(_))
+        ));
+
+        assert!(matches!(
+            png_text_from_memory(b"GIF89a"),
+            Err(Error::UnknownFormat)
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+//! Conversion of HDR images into 8-bit images with exposure control and tonemapping.
+//!
+//! stb converts HDR images loaded with 8-bit loaders with a fixed gamma curve clipping
+//! everything brighter than 1.0. [`HdrToLdr`] adds exposure and tonemapping curves, set it with
+//! [`LoadOptions::hdr_to_ldr`](super::LoadOptions::hdr_to_ldr) or apply it to float images with
+//! [`Image::into_ldr`].
+
+use super::{Data, Image};
+use std::mem;
+
+/// Curve compressing linear HDR values into the displayable `[0, 1]` range
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Tonemap {
+    /// Clips values above 1.0, same as stb's conversion
+    #[default]
+    Clamp,
+    /// Reinhard's `x / (1 + x)`, compresses highlights instead of clipping them
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve, more contrast than Reinhard
+    Aces,
+}
+
+/// Curves are flat long before this value, clamping to it keeps them finite for infinite input
+const MAX_INPUT: f32 = 1e6;
+
+impl Tonemap {
+    /// Maps linear `value` into `[0, 1]`, negative and NaN values map to zero
+    pub fn apply(self, value: f32) -> f32 {
+        // NaN fails the comparison, so it maps to zero as well
+        let x = if value > 0.0 {
+            value.min(MAX_INPUT)
+        } else {
+            0.0
+        };
+
+        let mapped = match self {
+            Tonemap::Clamp => x,
+            Tonemap::Reinhard => x / (1.0 + x),
+            Tonemap::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        };
+
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+/// Parameters of HDR to 8-bit conversion.
+///
+/// Color samples are multiplied by `scale * 2^exposure`, compressed with `tonemap` and gamma
+/// encoded with `1 / gamma`. Alpha samples are only clamped, the same way stb converts them.
+/// Default parameters match stb's default conversion.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HdrToLdr {
+    /// Exposure adjustment in stops (EV), each stop doubles the brightness (0.0 by default)
+    pub exposure: f32,
+    /// Linear scale applied along with exposure (1.0 by default)
+    pub scale: f32,
+    /// Curve compressing highlights ([`Tonemap::Clamp`] by default)
+    pub tonemap: Tonemap,
+    /// Display gamma (2.2 by default), 1.0 keeps output linear
+    pub gamma: f32,
+}
+
+impl Default for HdrToLdr {
+    fn default() -> Self {
+        HdrToLdr {
+            exposure: 0.0,
+            scale: 1.0,
+            tonemap: Tonemap::Clamp,
+            gamma: 2.2,
+        }
+    }
+}
+
+impl HdrToLdr {
+    /// Converts linear color sample `value` into 8-bit
+    pub fn color(&self, value: f32) -> u8 {
+        self.color_with(self.multiplier(), value)
+    }
+
+    /// Converts alpha sample `value` into 8-bit
+    pub fn alpha(&self, value: f32) -> u8 {
+        quantize(value)
+    }
+
+    fn multiplier(&self) -> f32 {
+        self.scale * self.exposure.exp2()
+    }
+
+    fn color_with(&self, multiplier: f32, value: f32) -> u8 {
+        let mapped = self.tonemap.apply(value * multiplier);
+        quantize(mapped.powf(1.0 / self.gamma))
+    }
+}
+
+/// Rounds `value` in `[0, 1]` to 8-bit, the cast saturates out of range values and maps NaN to 0
+fn quantize(value: f32) -> u8 {
+    (value * 255.0 + 0.5) as u8
+}
+
+/// Converts float samples in `data` with `channels` per pixel into 8-bit with `conversion`,
+/// reusing memory of `data`
+fn hdr_to_ldr(data: Data<f32>, channels: usize, conversion: HdrToLdr) -> Data<u8> {
+    let multiplier = conversion.multiplier();
+    let has_alpha = matches!(channels, 2 | 4);
+
+    let size = data.size;
+    let samples = data.data;
+    // Ownership of stb memory moves to the 8-bit data
+    mem::forget(data);
+    let ldr = Data::from_raw(samples as *mut u8, size);
+
+    for i in 0..size {
+        // SAFETY: byte `i` is written after float `i` is read and never overlaps floats not
+        // read yet, which start at byte `4 * (i + 1)`
+        unsafe {
+            let value = samples.add(i).read();
+            let sample = if has_alpha && i % channels == channels - 1 {
+                conversion.alpha(value)
+            } else {
+                conversion.color_with(multiplier, value)
+            };
+            ldr.data.add(i).write(sample);
+        }
+    }
+
+    ldr
+}
+
+impl Image<f32> {
+    /// Converts this HDR image into 8-bit with `conversion`.
+    /// The last channel of 2 and 4 channel images is alpha.
+    ///
+    /// Pixel memory is reused, so no allocation is made.
+    pub fn into_ldr(self, conversion: HdrToLdr) -> Image<u8> {
+        Image {
+            info: self.info,
+            width: self.width,
+            height: self.height,
+            channels: self.channels,
+            orientation: self.orientation,
+            data: hdr_to_ldr(self.data, self.channels, conversion),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tonemap_curves() {
+        assert_eq!(Tonemap::Clamp.apply(0.5), 0.5);
+        assert_eq!(Tonemap::Clamp.apply(3.0), 1.0);
+        assert_eq!(Tonemap::Reinhard.apply(1.0), 0.5);
+        assert!(Tonemap::Reinhard.apply(100.0) < 1.0);
+        assert!((Tonemap::Aces.apply(0.18) - 0.267).abs() < 0.001);
+        assert_eq!(Tonemap::Aces.apply(100.0), 1.0);
+
+        for tonemap in [Tonemap::Clamp, Tonemap::Reinhard, Tonemap::Aces] {
+            assert_eq!(tonemap.apply(0.0), 0.0);
+            assert_eq!(tonemap.apply(-1.0), 0.0);
+            assert_eq!(tonemap.apply(f32::NAN), 0.0);
+            assert!(tonemap.apply(f32::INFINITY) > 0.999);
+        }
+    }
+
+    #[test]
+    fn convert_samples() {
+        let linear = HdrToLdr {
+            gamma: 1.0,
+            ..HdrToLdr::default()
+        };
+        assert_eq!(linear.color(0.5), 128);
+        assert_eq!(linear.color(2.0), 255);
+
+        let darker = HdrToLdr {
+            exposure: -1.0,
+            ..linear
+        };
+        assert_eq!(darker.color(0.5), 64);
+        assert_eq!(darker.color(2.0), 255);
+
+        let scaled = HdrToLdr {
+            scale: 0.25,
+            exposure: 1.0,
+            ..linear
+        };
+        assert_eq!(scaled.color(1.0), 128);
+
+        // Default gamma brightens mid tones
+        assert_eq!(HdrToLdr::default().color(0.5), 186);
+
+        // Alpha is neither exposed nor gamma encoded
+        assert_eq!(darker.alpha(0.5), 128);
+        assert_eq!(darker.alpha(2.0), 255);
+    }
+}
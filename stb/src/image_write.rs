@@ -7,10 +7,18 @@
 //! not optimal image file size or run-time performance.
 
 use stb_sys as sys;
+use std::any::Any;
+use std::convert::TryFrom;
 use std::ffi::c_void;
-use std::ffi::CStr;
+use std::ffi::{CStr, OsString};
+use std::fs;
+use std::io::{self, Write};
 use std::os::raw;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub fn stbi_write_png(
     filename: &CStr,
@@ -111,15 +119,57 @@ pub fn stbi_write_jpg(
     }
 }
 
-extern "C" fn write_func<F, T>(context: *mut raw::c_void, data: *mut raw::c_void, size: raw::c_int)
+/// Context passed to stb along with `write_func`
+struct Context<'a, F> {
+    func: &'a mut F,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+/// stb passes encoded bytes to the callback, whatever the type of image elements is
+extern "C" fn write_func<F>(context: *mut raw::c_void, data: *mut raw::c_void, size: raw::c_int)
 where
-    F: FnMut(&[T]),
+    F: FnMut(&[u8]),
 {
-    let buffer = unsafe { slice::from_raw_parts_mut(data as *mut T, size as _) };
+    let buffer = unsafe { slice::from_raw_parts(data as *const u8, size as _) };
     // See https://s3.amazonaws.com/temp.michaelfbryan.com/callbacks/index.html
-    let f: &mut F = unsafe { &mut *(context as *mut F) };
+    let context: &mut Context<F> = unsafe { &mut *(context as *mut Context<F>) };
+
+    // Skip remaining writes once the function panicked
+    if context.panic.is_some() {
+        return;
+    }
+
+    let func = &mut *context.func;
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| func(buffer))) {
+        context.panic = Some(payload);
+    }
+}
+
+/// Runs stb `write` function with `func` as the write callback.
+/// Panics can't unwind through stb's C frames, so a panic in `func` is caught and resumed
+/// once `write` returns.
+fn write_to_func<F>(
+    func: &mut F,
+    write: impl FnOnce(sys::stbi_write_func, *mut c_void) -> raw::c_int,
+) -> Option<()>
+where
+    F: FnMut(&[u8]),
+{
+    let mut context = Context { func, panic: None };
+    let ret = write(
+        Some(write_func::<F>),
+        &mut context as *mut Context<F> as *mut c_void,
+    );
 
-    f(buffer)
+    if let Some(payload) = context.panic {
+        panic::resume_unwind(payload);
+    }
+
+    if ret == 0 {
+        None
+    } else {
+        Some(())
+    }
 }
 
 pub fn stbi_write_png_to_func<F>(
@@ -133,23 +183,17 @@ pub fn stbi_write_png_to_func<F>(
 where
     F: FnMut(&[u8]),
 {
-    let ret = unsafe {
+    write_to_func(func, |write, context| unsafe {
         sys::stbi_write_png_to_func(
-            Some(write_func::<F, u8>),
-            func as *mut F as *mut c_void,
+            write,
+            context,
             w,
             h,
             comp,
             buffer.as_ptr() as *const c_void,
             stride_in_bytes,
         )
-    };
-
-    if ret == 0 {
-        None
-    } else {
-        Some(())
-    }
+    })
 }
 
 pub fn stbi_write_bmp_to_func<F>(
@@ -162,22 +206,9 @@ pub fn stbi_write_bmp_to_func<F>(
 where
     F: FnMut(&[u8]),
 {
-    let ret = unsafe {
-        sys::stbi_write_bmp_to_func(
-            Some(write_func::<F, u8>),
-            func as *mut F as *mut c_void,
-            w,
-            h,
-            comp,
-            buffer.as_ptr() as *const c_void,
-        )
-    };
-
-    if ret == 0 {
-        None
-    } else {
-        Some(())
-    }
+    write_to_func(func, |write, context| unsafe {
+        sys::stbi_write_bmp_to_func(write, context, w, h, comp, buffer.as_ptr() as *const c_void)
+    })
 }
 
 pub fn stbi_write_tga_to_func<F>(
@@ -190,24 +221,12 @@ pub fn stbi_write_tga_to_func<F>(
 where
     F: FnMut(&[u8]),
 {
-    let ret = unsafe {
-        sys::stbi_write_tga_to_func(
-            Some(write_func::<F, u8>),
-            func as *mut F as *mut c_void,
-            w,
-            h,
-            comp,
-            buffer.as_ptr() as *const c_void,
-        )
-    };
-
-    if ret == 0 {
-        None
-    } else {
-        Some(())
-    }
+    write_to_func(func, |write, context| unsafe {
+        sys::stbi_write_tga_to_func(write, context, w, h, comp, buffer.as_ptr() as *const c_void)
+    })
 }
 
+/// Encodes HDR image from float `buffer`, `func` receives bytes of the encoded file
 pub fn stbi_write_hdr_to_func<F>(
     func: &mut F,
     w: i32,
@@ -216,24 +235,11 @@ pub fn stbi_write_hdr_to_func<F>(
     buffer: &[f32],
 ) -> Option<()>
 where
-    F: FnMut(&[f32]),
+    F: FnMut(&[u8]),
 {
-    let ret = unsafe {
-        sys::stbi_write_hdr_to_func(
-            Some(write_func::<F, f32>),
-            func as *mut F as *mut c_void,
-            w,
-            h,
-            comp,
-            buffer.as_ptr() as *const f32,
-        )
-    };
-
-    if ret == 0 {
-        None
-    } else {
-        Some(())
-    }
+    write_to_func(func, |write, context| unsafe {
+        sys::stbi_write_hdr_to_func(write, context, w, h, comp, buffer.as_ptr() as *const f32)
+    })
 }
 
 pub fn stbi_write_jpg_to_func<F>(
@@ -247,31 +253,207 @@ pub fn stbi_write_jpg_to_func<F>(
 where
     F: FnMut(&[u8]),
 {
-    let ret = unsafe {
+    write_to_func(func, |write, context| unsafe {
         sys::stbi_write_jpg_to_func(
-            Some(write_func::<F, u8>),
-            func as *mut F as *mut c_void,
+            write,
+            context,
             w,
             h,
             comp,
             buffer.as_ptr() as *const c_void,
             quality,
         )
+    })
+}
+
+/// Runs `write` with a function that writes to a temporary file next to `path`, which replaces
+/// `path` once the image is encoded. An existing file is left intact if encoding fails.
+fn write_path<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut dyn FnMut(&[u8])) -> Option<()>,
+{
+    let temp = temp_path(path)?;
+    let result = write_file(&temp, write).and_then(|()| fs::rename(&temp, path));
+
+    if result.is_err() {
+        // Temporary file is missing if it failed to be created
+        let _ = fs::remove_file(&temp);
+    }
+
+    result
+}
+
+/// Returns a unique hidden path in the directory of `path`, so it can be renamed to `path`
+fn temp_path(path: &Path) -> io::Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path doesn't name a file"))?;
+
+    let mut name = OsString::from(".");
+    name.push(file_name);
+    name.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    Ok(path.with_file_name(name))
+}
+
+/// Creates file at `path` and runs `write` with a function that writes to the file.
+/// `write` is expected to return `None` if stb failed to encode the image.
+fn write_file<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut dyn FnMut(&[u8])) -> Option<()>,
+{
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    let mut file = io::BufWriter::new(file);
+    let mut result = Ok(());
+
+    let encoded = write(&mut |data: &[u8]| {
+        if result.is_ok() {
+            result = file.write_all(data);
+        }
+    });
+
+    result?;
+
+    if encoded.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "failed to encode image",
+        ));
+    }
+
+    file.into_inner()
+        .map_err(|err| err.into_error())?
+        .sync_all()
+}
+
+/// Checks that `len` elements of the buffer hold `h` rows of `w` x `comp` elements placed
+/// `stride` elements apart (zero means tightly packed rows), so stb doesn't read past it
+fn check_buffer(len: usize, w: i32, h: i32, comp: i32, stride: i32) -> io::Result<()> {
+    fn required(w: usize, h: usize, comp: usize, stride: usize) -> Option<usize> {
+        let row = w.checked_mul(comp)?;
+        let stride = if stride == 0 { row } else { stride };
+
+        match h.checked_sub(1) {
+            Some(rows) => stride.checked_mul(rows)?.checked_add(row),
+            None => Some(0),
+        }
+    }
+
+    let invalid = |message| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+
+    if !(1..=4).contains(&comp) {
+        return invalid("number of components must be between 1 and 4");
+    }
+
+    let dims = [w, h, comp, stride].map(usize::try_from);
+    let required = match dims {
+        [Ok(w), Ok(h), Ok(comp), Ok(stride)] => required(w, h, comp, stride),
+        _ => return invalid("negative image dimensions"),
     };
 
-    if ret == 0 {
-        None
-    } else {
-        Some(())
+    match required {
+        Some(required) if required <= len => Ok(()),
+        _ => invalid("buffer is smaller than the image"),
     }
 }
 
+/// Writes PNG to file at `path` using `std::fs` rather than C stdio
+pub fn write_png_path(
+    path: impl AsRef<Path>,
+    w: i32,
+    h: i32,
+    comp: i32,
+    buffer: &[u8],
+    stride_in_bytes: i32,
+) -> io::Result<()> {
+    check_buffer(buffer.len(), w, h, comp, stride_in_bytes)?;
+
+    write_path(path.as_ref(), |mut func| {
+        stbi_write_png_to_func(&mut func, w, h, comp, buffer, stride_in_bytes)
+    })
+}
+
+/// Writes BMP to file at `path` using `std::fs` rather than C stdio
+pub fn write_bmp_path(
+    path: impl AsRef<Path>,
+    w: i32,
+    h: i32,
+    comp: i32,
+    buffer: &[u8],
+) -> io::Result<()> {
+    check_buffer(buffer.len(), w, h, comp, 0)?;
+
+    write_path(path.as_ref(), |mut func| {
+        stbi_write_bmp_to_func(&mut func, w, h, comp, buffer)
+    })
+}
+
+/// Writes TGA to file at `path` using `std::fs` rather than C stdio
+pub fn write_tga_path(
+    path: impl AsRef<Path>,
+    w: i32,
+    h: i32,
+    comp: i32,
+    buffer: &[u8],
+) -> io::Result<()> {
+    check_buffer(buffer.len(), w, h, comp, 0)?;
+
+    write_path(path.as_ref(), |mut func| {
+        stbi_write_tga_to_func(&mut func, w, h, comp, buffer)
+    })
+}
+
+/// Writes HDR to file at `path` using `std::fs` rather than C stdio
+pub fn write_hdr_path(
+    path: impl AsRef<Path>,
+    w: i32,
+    h: i32,
+    comp: i32,
+    buffer: &[f32],
+) -> io::Result<()> {
+    check_buffer(buffer.len(), w, h, comp, 0)?;
+
+    write_path(path.as_ref(), |mut func| {
+        stbi_write_hdr_to_func(&mut func, w, h, comp, buffer)
+    })
+}
+
+/// Writes JPEG to file at `path` using `std::fs` rather than C stdio
+pub fn write_jpg_path(
+    path: impl AsRef<Path>,
+    w: i32,
+    h: i32,
+    comp: i32,
+    buffer: &[u8],
+    quality: i32,
+) -> io::Result<()> {
+    check_buffer(buffer.len(), w, h, comp, 0)?;
+
+    write_path(path.as_ref(), |mut func| {
+        stbi_write_jpg_to_func(&mut func, w, h, comp, buffer, quality)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::ffi::CString;
     use std::fs;
 
+    /// Returns a path in the temporary directory unique to this test process
+    fn unique_temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("stb_{}_{}", process::id(), name))
+    }
+
     #[test]
     fn write_bmp() {
         let mut dir = std::env::temp_dir();
@@ -301,4 +483,114 @@ mod tests {
         .expect("Failed to write BMP to func");
         assert_ne!(counter, 0);
     }
+
+    #[test]
+    fn write_callback_panic() {
+        let mut calls = 0;
+        let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            stbi_write_png_to_func(
+                &mut |_data| {
+                    calls += 1;
+                    panic!("write panic");
+                },
+                2,
+                2,
+                1,
+                &[0, 1, 2, 3],
+                2,
+            )
+        }))
+        .expect_err("Write panic must be resumed");
+
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"write panic"));
+        // Writes following the panic are skipped
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn write_png_path() {
+        let path = unique_temp_path("write_png_path.png");
+
+        super::write_png_path(&path, 2, 1, 3, &[255, 0, 0, 0, 255, 0], 6)
+            .expect("Failed to write PNG");
+
+        let data = fs::read(&path).expect("Failed to read PNG file");
+        assert!(data.starts_with(b"\x89PNG"));
+        fs::remove_file(&path).expect("Failed to remove PNG file");
+    }
+
+    #[test]
+    #[cfg(all(feature = "stb_image", not(feature = "stbi_no_linear")))]
+    fn write_hdr_path_round_trip() {
+        use crate::image::{stbi_loadf_from_memory, Channels, LoadOptions};
+
+        let path = unique_temp_path("write_hdr_path.hdr");
+        // Values sharing an exponent survive RGBE encoding exactly
+        let pixels = [1.0, 0.5, 0.25, 2.0, 1.0, 0.5];
+
+        super::write_hdr_path(&path, 2, 1, 3, &pixels).expect("Failed to write HDR");
+
+        let data = fs::read(&path).expect("Failed to read HDR file");
+        fs::remove_file(&path).expect("Failed to remove HDR file");
+        assert!(data.starts_with(b"#?RADIANCE\n"));
+
+        let image = stbi_loadf_from_memory(&data, Channels::Rgb, LoadOptions::default())
+            .expect("Failed to load HDR");
+        assert_eq!((image.width(), image.height()), (2, 1));
+        assert_eq!(image.as_slice(), &pixels);
+    }
+
+    #[test]
+    fn write_path_short_buffer() {
+        let path = unique_temp_path("write_path_short_buffer.png");
+
+        let err = super::write_png_path(&path, 2, 2, 3, &[0; 11], 0)
+            .expect_err("Buffer is smaller than the image");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // Second row starts 8 bytes in
+        let err = super::write_png_path(&path, 2, 2, 3, &[0; 13], 8)
+            .expect_err("Buffer is smaller than the strided image");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let err = super::write_hdr_path(&path, 1, 1, 3, &[0.0; 2])
+            .expect_err("Buffer is smaller than the image");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let err = super::write_bmp_path(&path, -1, 1, 3, &[0; 3]).expect_err("Negative width");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn write_path_keeps_file() {
+        let dir = unique_temp_path("write_path_keeps_file");
+        fs::create_dir_all(&dir).expect("Failed to create directory");
+        let path = dir.join("test.jpg");
+        fs::write(&path, b"previous").expect("Failed to write file");
+
+        // stb refuses to encode empty JPEG
+        let err = super::write_jpg_path(&path, 0, 1, 3, &[0; 3], 90)
+            .expect_err("Empty image must not be encoded");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(fs::read(&path).expect("Failed to read file"), b"previous");
+
+        // Temporary file is removed
+        let files = fs::read_dir(&dir)
+            .expect("Failed to list directory")
+            .count();
+        assert_eq!(files, 1);
+        fs::remove_dir_all(&dir).expect("Failed to remove directory");
+    }
+
+    #[test]
+    fn write_path_error() {
+        let mut path = std::env::temp_dir();
+        path.push("missing_dir");
+        path.push("test.bmp");
+
+        let err = super::write_bmp_path(&path, 1, 1, 1, &[1]).expect_err("Directory is missing");
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
 }
@@ -35,3 +35,21 @@ pub mod image;
 /// Image writing to disk: PNG, TGA, BMP
 #[cfg(feature = "stb_image_write")]
 pub mod image_write;
+
+/// zlib compression and decompression
+#[cfg(any(
+    all(
+        feature = "stb_image",
+        any(not(feature = "stbi_no_png"), feature = "stbi_support_zlib")
+    ),
+    feature = "stb_image_write"
+))]
+pub mod zlib;
+
+/// Length of `buffer` as stb sees it, `None` if it exceeds `int` stb takes buffer sizes as
+#[cfg(any(feature = "stb_image", feature = "stb_image_write"))]
+fn buffer_len(buffer: &[u8]) -> Option<std::os::raw::c_int> {
+    use std::convert::TryFrom;
+
+    std::os::raw::c_int::try_from(buffer.len()).ok()
+}
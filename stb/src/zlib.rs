@@ -0,0 +1,60 @@
+//! zlib (RFC 1950) and raw deflate (RFC 1951) compression and decompression.
+//!
+//! Decompression is backed by stb_image's zlib decoder (requires `stb_image` feature and isn't
+//! available with `stbi_no_png` unless `stbi_support_zlib` is enabled), compression is backed
+//! by stb_image_write's encoder (requires `stb_image_write` feature).
+//!
+//! stb's encoder is tuned for source code compactness rather than compression ratio, so expect
+//! larger output compared to zlib.
+
+use std::error;
+use std::fmt;
+
+/// Errors reported by zlib functions
+#[derive(Debug)]
+pub enum Error {
+    /// Input is not a valid compressed stream, holds the stb failure reason
+    Corrupt(&'static str),
+    /// Output buffer is too small to hold decompressed data
+    BufferTooSmall,
+    /// stb failed to allocate memory
+    OutOfMemory,
+    /// Input or output is larger than stb is able to handle (`i32::MAX` bytes)
+    TooLarge,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Corrupt(reason) => write!(f, "corrupt stream: {}", reason),
+            Error::BufferTooSmall => write!(f, "output buffer is too small"),
+            Error::OutOfMemory => write!(f, "out of memory"),
+            Error::TooLarge => write!(f, "data is too large"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// A specialized `Result` type for zlib functions
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(all(
+    feature = "stb_image",
+    any(not(feature = "stbi_no_png"), feature = "stbi_support_zlib")
+))]
+mod inflate;
+#[cfg(all(
+    feature = "stb_image",
+    any(not(feature = "stbi_no_png"), feature = "stbi_support_zlib")
+))]
+pub use inflate::{inflate, inflate_into, inflate_limited, inflate_raw, inflate_raw_into};
+
+#[cfg(feature = "stb_image_write")]
+mod deflate;
+#[cfg(feature = "stb_image_write")]
+pub use deflate::deflate;
+
+/// Sample text shared by tests of both directions
+#[cfg(test)]
+const TEXT: &[u8] = b"hello hello hello hello";
@@ -0,0 +1,48 @@
+//! Compression backed by stb_image_write's zlib encoder.
+
+use super::{Error, Result};
+use crate::buffer_len;
+use stb_sys as sys;
+use std::os::raw;
+use std::slice;
+
+/// Compresses `data` into zlib stream (with zlib header).
+/// `quality` controls how hard the encoder searches for matches, values below 5 are treated
+/// as 5 (PNG writer uses 8 by default).
+pub fn deflate(data: &[u8], quality: i32) -> Result<Vec<u8>> {
+    let mut out_len = 0;
+
+    // stb doesn't modify input, it just lacks const qualifier
+    let compressed = unsafe {
+        sys::stbi_zlib_compress(
+            data.as_ptr() as *mut u8,
+            buffer_len(data).ok_or(Error::TooLarge)?,
+            &mut out_len,
+            quality,
+        )
+    };
+
+    if compressed.is_null() {
+        return Err(Error::OutOfMemory);
+    }
+
+    let vec = unsafe { slice::from_raw_parts(compressed as *const u8, out_len as usize) }.to_vec();
+    unsafe { sys::stbi_write_free(compressed as *mut raw::c_void) };
+
+    Ok(vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zlib::TEXT;
+
+    #[test]
+    fn deflate_zlib_header() {
+        let compressed = deflate(TEXT, 8).expect("Failed to deflate");
+
+        // Deflate compression method with 32K window, header checksum is a multiple of 31
+        assert_eq!(compressed[0], 0x78);
+        assert_eq!(u16::from_be_bytes([compressed[0], compressed[1]]) % 31, 0);
+    }
+}
@@ -0,0 +1,167 @@
+//! Decompression backed by stb_image's zlib decoder.
+
+use super::{Error, Result};
+use crate::buffer_len;
+use stb_sys as sys;
+use std::os::raw;
+use std::slice;
+
+impl Error {
+    /// Builds an error from the reason of the last failure reported by stb on this thread
+    fn from_failure_reason() -> Self {
+        match crate::image::failure_reason() {
+            "output buffer limit" => Error::BufferTooSmall,
+            "outofmem" => Error::OutOfMemory,
+            reason => Error::Corrupt(reason),
+        }
+    }
+}
+
+/// Signature of `stbi_zlib_decode*_malloc` functions
+type DecodeMalloc =
+    unsafe extern "C" fn(*const raw::c_char, raw::c_int, *mut raw::c_int) -> *mut raw::c_char;
+
+/// Signature of `stbi_zlib_decode*_buffer` functions
+type DecodeBuffer = unsafe extern "C" fn(
+    *mut raw::c_char,
+    raw::c_int,
+    *const raw::c_char,
+    raw::c_int,
+) -> raw::c_int;
+
+fn decode_malloc(input: &[u8], decode: DecodeMalloc) -> Result<Vec<u8>> {
+    let mut out_len = 0;
+    let data = unsafe {
+        decode(
+            input.as_ptr() as *const raw::c_char,
+            buffer_len(input).ok_or(Error::TooLarge)?,
+            &mut out_len,
+        )
+    };
+
+    if data.is_null() {
+        return Err(Error::from_failure_reason());
+    }
+
+    let vec = unsafe { slice::from_raw_parts(data as *const u8, out_len as usize) }.to_vec();
+    unsafe { sys::stbi_image_free(data as *mut raw::c_void) };
+
+    Ok(vec)
+}
+
+fn decode_buffer(input: &[u8], output: &mut [u8], decode: DecodeBuffer) -> Result<usize> {
+    // Larger buffers can't be fully used anyway
+    let output_len = output.len().min(raw::c_int::MAX as usize) as raw::c_int;
+
+    let ret = unsafe {
+        decode(
+            output.as_mut_ptr() as *mut raw::c_char,
+            output_len,
+            input.as_ptr() as *const raw::c_char,
+            buffer_len(input).ok_or(Error::TooLarge)?,
+        )
+    };
+
+    if ret < 0 {
+        Err(Error::from_failure_reason())
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+/// Decompresses zlib stream (with zlib header)
+pub fn inflate(input: &[u8]) -> Result<Vec<u8>> {
+    decode_malloc(input, sys::stbi_zlib_decode_malloc)
+}
+
+/// Decompresses zlib stream (with zlib header) into `output`.
+/// Returns the number of bytes written.
+pub fn inflate_into(input: &[u8], output: &mut [u8]) -> Result<usize> {
+    decode_buffer(input, output, sys::stbi_zlib_decode_buffer)
+}
+
+/// Decompresses zlib stream (with zlib header) that must not expand past `limit` bytes.
+/// Fails with [`Error::BufferTooSmall`] once the limit is reached, so untrusted streams
+/// can't exhaust memory.
+pub fn inflate_limited(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    // Stream is decoded once into a buffer of the limit size. It's allocated zeroed, so pages
+    // past the decompressed data are typically never touched and cost no memory.
+    let mut output = vec![0; limit.min(raw::c_int::MAX as usize)];
+    let len = inflate_into(input, &mut output)?;
+
+    output.truncate(len);
+    output.shrink_to_fit();
+    Ok(output)
+}
+
+/// Decompresses raw deflate stream (without zlib header)
+pub fn inflate_raw(input: &[u8]) -> Result<Vec<u8>> {
+    decode_malloc(input, sys::stbi_zlib_decode_noheader_malloc)
+}
+
+/// Decompresses raw deflate stream (without zlib header) into `output`.
+/// Returns the number of bytes written.
+pub fn inflate_raw_into(input: &[u8], output: &mut [u8]) -> Result<usize> {
+    decode_buffer(input, output, sys::stbi_zlib_decode_noheader_buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zlib::TEXT;
+
+    // zlib.compress(TEXT)
+    const ZLIB: &[u8] = &[
+        120, 156, 203, 72, 205, 201, 201, 87, 200, 64, 39, 1, 104, 3, 8, 177,
+    ];
+
+    // Same stream without zlib header and checksum
+    const RAW: &[u8] = &[203, 72, 205, 201, 201, 87, 200, 64, 39, 1];
+
+    #[test]
+    fn inflate_zlib() {
+        assert_eq!(inflate(ZLIB).expect("Failed to inflate"), TEXT);
+
+        let mut output = [0; 64];
+        let len = inflate_into(ZLIB, &mut output).expect("Failed to inflate into buffer");
+        assert_eq!(&output[..len], TEXT);
+    }
+
+    #[test]
+    fn inflate_raw_deflate() {
+        assert_eq!(inflate_raw(RAW).expect("Failed to inflate"), TEXT);
+
+        let mut output = [0; 64];
+        let len = inflate_raw_into(RAW, &mut output).expect("Failed to inflate into buffer");
+        assert_eq!(&output[..len], TEXT);
+    }
+
+    #[test]
+    fn inflate_errors() {
+        let mut output = [0; 4];
+        let err = inflate_into(ZLIB, &mut output).expect_err("Output buffer must be too small");
+        assert!(matches!(err, Error::BufferTooSmall));
+
+        let err = inflate(&[0, 1, 2, 3]).expect_err("Garbage must not be inflated");
+        assert!(matches!(err, Error::Corrupt(_)));
+    }
+
+    #[test]
+    fn inflate_with_limit() {
+        let output = inflate_limited(ZLIB, TEXT.len()).expect("Failed to inflate");
+        assert_eq!(output, TEXT);
+
+        let err = inflate_limited(ZLIB, TEXT.len() - 1).expect_err("Output must exceed limit");
+        assert!(matches!(err, Error::BufferTooSmall));
+    }
+
+    #[test]
+    #[cfg(feature = "stb_image_write")]
+    fn deflate_round_trip() {
+        let data = TEXT.repeat(100);
+        let compressed = crate::zlib::deflate(&data, 8).expect("Failed to deflate");
+
+        assert!(compressed.len() < data.len());
+        assert_eq!(inflate(&compressed).expect("Failed to inflate"), data);
+    }
+}
@@ -0,0 +1,22 @@
+//! Fixture helpers shared by integration tests, benches and unit tests of the `image` module.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Returns the path of `file` relative to `tests/fixtures` at the repository root
+pub fn fixture_path(file: &str) -> PathBuf {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let mut path = PathBuf::from(root.parent().unwrap());
+
+    path.push("tests/fixtures");
+    path.push(file);
+
+    path
+}
+
+pub fn fixture(file: &str) -> Vec<u8> {
+    let path = fixture_path(file);
+    fs::read(&path).unwrap_or_else(|err| panic!("Failed to read {}: {}", path.display(), err))
+}
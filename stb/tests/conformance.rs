@@ -0,0 +1,325 @@
+//! Decodes fixtures of every supported format and bit depth from `tests/fixtures/conformance`
+//! with memory and reader loaders and compares decoded pixels with expected hashes.
+//! Cases of formats disabled with `stbi_no_*` features are skipped.
+//!
+//! Fixtures are 13x11 images, odd dimensions catch row padding and partial block bugs. Most of
+//! them encode the same pattern, so hashes of e.g. `png-rgb8.png` and `bmp-rgb24.bmp` match.
+//! Expected hashes were cross-checked with independent decoders where one exists. JPEG is
+//! lossy and decoders may round differently, so JPEG fixtures are compared with the source
+//! pattern within a tolerance instead.
+
+#![cfg(feature = "stb_image")]
+
+mod common;
+
+use stb::image::{self, Channels, Image, ImageFormat, LoadOptions, Result};
+use std::fmt::Debug;
+
+/// Sample type of the loader matching the native precision of a fixture
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Depth {
+    U8,
+    U16,
+    F32,
+}
+
+use Depth::{F32, U16, U8};
+use ImageFormat::{Bmp, Gif, Hdr, Pic, Png, Pnm, Psd, Tga};
+
+#[derive(Debug)]
+struct Case {
+    file: &'static str,
+    format: ImageFormat,
+    width: usize,
+    height: usize,
+    /// Number of components reported by stb, also the number of decoded channels
+    components: usize,
+    depth: Depth,
+    /// FNV-1a hash of samples decoded with `Channels::Default`, as little endian bytes
+    hash: u64,
+}
+
+const fn case(
+    file: &'static str,
+    format: ImageFormat,
+    width: usize,
+    height: usize,
+    components: usize,
+    depth: Depth,
+    hash: u64,
+) -> Case {
+    Case {
+        file,
+        format,
+        width,
+        height,
+        components,
+        depth,
+        hash,
+    }
+}
+
+#[rustfmt::skip]
+const CASES: &[Case] = &[
+    case("png-gray1.png", Png, 13, 11, 1, U8, 0x95e3eb44d0a10ed8),
+    case("png-gray2.png", Png, 13, 11, 1, U8, 0x43a0b2d3ba6d06c2),
+    case("png-gray4.png", Png, 13, 11, 1, U8, 0x8dc9e2d6087930ee),
+    case("png-gray8.png", Png, 13, 11, 1, U8, 0x3d43a0b892017414),
+    case("png-gray16.png", Png, 13, 11, 1, U16, 0x3129125ad0a99f11),
+    case("png-gray16-interlaced.png", Png, 13, 11, 1, U16, 0x3129125ad0a99f11),
+    case("png-gray-alpha8.png", Png, 13, 11, 2, U8, 0x4304b9bc1112efad),
+    case("png-gray-alpha16.png", Png, 13, 11, 2, U16, 0x1e6c80dacd4c8626),
+    case("png-rgb8.png", Png, 13, 11, 3, U8, 0x4ab188540c91e63a),
+    case("png-rgb8-interlaced.png", Png, 13, 11, 3, U8, 0x4ab188540c91e63a),
+    case("png-rgb16.png", Png, 13, 11, 3, U16, 0x861913ee6f3167bb),
+    case("png-rgba8.png", Png, 13, 11, 4, U8, 0x4d4bb3fc6e7163d7),
+    case("png-rgba16.png", Png, 13, 11, 4, U16, 0xcaa801e6b8777f8c),
+    case("png-palette1.png", Png, 13, 11, 3, U8, 0x8127d882cacc32ed),
+    case("png-palette2.png", Png, 13, 11, 3, U8, 0xfd20a3d424fe0fed),
+    case("png-palette4.png", Png, 13, 11, 3, U8, 0xf450b04856387995),
+    case("png-palette8.png", Png, 13, 11, 3, U8, 0x534732442a86ecb5),
+    case("png-palette2-interlaced.png", Png, 13, 11, 3, U8, 0xfd20a3d424fe0fed),
+    case("png-palette4-trns.png", Png, 13, 11, 4, U8, 0x4336e2a804660ebc),
+    case("tga-rgb24.tga", Tga, 13, 11, 3, U8, 0x4ab188540c91e63a),
+    case("tga-rgba32.tga", Tga, 13, 11, 4, U8, 0x4d4bb3fc6e7163d7),
+    case("tga-rgb24-rle.tga", Tga, 13, 11, 3, U8, 0x340f12f91bb07a84),
+    case("tga-colormap8.tga", Tga, 13, 11, 3, U8, 0x21fd6abfba3dc175),
+    case("tga-colormap8-rle.tga", Tga, 13, 11, 3, U8, 0xf19ac3d5680a7232),
+    case("tga-gray8.tga", Tga, 13, 11, 1, U8, 0x3d43a0b892017414),
+    case("tga-gray8-rle.tga", Tga, 13, 11, 1, U8, 0xb0fa0e15d7493bc6),
+    case("bmp-rgb24.bmp", Bmp, 13, 11, 3, U8, 0x4ab188540c91e63a),
+    case("bmp-rgb24-top-down.bmp", Bmp, 13, 11, 3, U8, 0x4ab188540c91e63a),
+    case("bmp-rgb16-555.bmp", Bmp, 13, 11, 3, U8, 0xb4e7e2f36ccde1d4),
+    case("bmp-rgb16-565.bmp", Bmp, 13, 11, 3, U8, 0x4b7ce47f1a1dc708),
+    case("bmp-rgba32-bitfields.bmp", Bmp, 13, 11, 4, U8, 0x4d4bb3fc6e7163d7),
+    case("bmp-palette8.bmp", Bmp, 13, 11, 3, U8, 0x534732442a86ecb5),
+    case("bmp-palette4.bmp", Bmp, 13, 11, 3, U8, 0xf450b04856387995),
+    case("psd-rgb8.psd", Psd, 13, 11, 4, U8, 0xe01182f4fa0061dd),
+    case("psd-rgba8-rle.psd", Psd, 13, 11, 4, U8, 0x524cd5f4c26b20ca),
+    case("psd-rgb16.psd", Psd, 13, 11, 4, U16, 0x1ea4f26502e18349),
+    case("psd-rgba16.psd", Psd, 13, 11, 4, U16, 0xdff11f7d477f6519),
+    case("hdr-flat.hdr", Hdr, 13, 11, 3, F32, 0x50e71cee77d55728),
+    case("hdr-rle.hdr", Hdr, 24, 6, 3, F32, 0xee72fb67e0970c95),
+    case("pic-rgba.pic", Pic, 13, 11, 4, U8, 0x4d956e7436b00588),
+    case("pic-rgb-rle.pic", Pic, 13, 11, 3, U8, 0x340f12f91bb07a84),
+    case("pnm-gray.pgm", Pnm, 13, 11, 1, U8, 0x3d43a0b892017414),
+    case("pnm-rgb.ppm", Pnm, 13, 11, 3, U8, 0x4ab188540c91e63a),
+    case("gif-palette.gif", Gif, 13, 11, 4, U8, 0x17431e138aaa0336),
+];
+
+fn fixture(file: &str) -> Vec<u8> {
+    common::fixture(&format!("conformance/{}", file))
+}
+
+/// 64-bit FNV-1a hash
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Decodes `data` from memory and from reader, both must produce the same image of `case`
+fn decode<T: PartialEq + Debug>(
+    case: &Case,
+    data: &[u8],
+    from_memory: impl Fn(&[u8]) -> Result<Image<T>>,
+    from_reader: impl Fn(&mut &[u8]) -> Result<Image<T>>,
+) -> Image<T> {
+    let image = from_memory(data).expect("Failed to load image from memory");
+    let from_reader = from_reader(&mut &data[..]).expect("Failed to load image from reader");
+
+    assert_eq!(
+        (image.width(), image.height(), image.channels()),
+        (case.width, case.height, case.components)
+    );
+    assert_eq!(image.as_slice(), from_reader.as_slice());
+
+    image
+}
+
+fn check_info(case: &Case, data: &[u8]) {
+    assert_eq!(image::detect_format(data), Some(case.format));
+
+    let info = image::stbi_info_from_memory(data).expect("Failed to read info from memory");
+    let reader_info =
+        image::stbi_info_from_reader(&mut &data[..]).expect("Failed to read info from reader");
+
+    for info in [info, reader_info] {
+        assert_eq!(
+            (info.width, info.height, info.components),
+            (
+                case.width as i32,
+                case.height as i32,
+                case.components as i32
+            )
+        );
+    }
+
+    assert_eq!(image::stbi_is_16_bit_from_memory(data), case.depth == U16);
+    assert_eq!(
+        image::stbi_is_16_bit_from_reader(&mut &data[..]),
+        case.depth == U16
+    );
+    assert_eq!(image::stbi_is_hdr_from_memory(data), case.depth == F32);
+    assert_eq!(
+        image::stbi_is_hdr_from_reader(&mut &data[..]),
+        case.depth == F32
+    );
+}
+
+fn check_pixels(case: &Case, data: &[u8]) {
+    let options = LoadOptions::default;
+
+    match case.depth {
+        U8 => {
+            let decoded = decode(
+                case,
+                data,
+                |data| image::stbi_load_from_memory(data, Channels::Default, options()),
+                |reader| image::stbi_load_from_reader(reader, Channels::Default, options()),
+            );
+            assert_eq!(fnv1a(decoded.iter().copied()), case.hash);
+
+            // 16-bit loaders scale 8-bit samples to the full range
+            let wide = decode(
+                case,
+                data,
+                |data| image::stbi_load_16_from_memory(data, Channels::Default, options()),
+                |reader| image::stbi_load_16_from_reader(reader, Channels::Default, options()),
+            );
+            assert!(decoded
+                .iter()
+                .zip(wide.iter())
+                .all(|(narrow, wide)| u16::from(*narrow) * 257 == *wide));
+        }
+        U16 => {
+            let decoded = decode(
+                case,
+                data,
+                |data| image::stbi_load_16_from_memory(data, Channels::Default, options()),
+                |reader| image::stbi_load_16_from_reader(reader, Channels::Default, options()),
+            );
+            assert_eq!(
+                fnv1a(decoded.iter().flat_map(|sample| sample.to_le_bytes())),
+                case.hash
+            );
+
+            // 8-bit loaders keep the high byte of 16-bit samples
+            let narrow = decode(
+                case,
+                data,
+                |data| image::stbi_load_from_memory(data, Channels::Default, options()),
+                |reader| image::stbi_load_from_reader(reader, Channels::Default, options()),
+            );
+            assert!(decoded
+                .iter()
+                .zip(narrow.iter())
+                .all(|(wide, narrow)| (*wide >> 8) as u8 == *narrow));
+        }
+        #[cfg(not(feature = "stbi_no_linear"))]
+        F32 => {
+            let decoded = decode(
+                case,
+                data,
+                |data| image::stbi_loadf_from_memory(data, Channels::Default, options()),
+                |reader| image::stbi_loadf_from_reader(reader, Channels::Default, options()),
+            );
+            assert_eq!(
+                fnv1a(
+                    decoded
+                        .iter()
+                        .flat_map(|sample| sample.to_bits().to_le_bytes())
+                ),
+                case.hash
+            );
+        }
+        // Float loaders are disabled, HDR images decode to 8-bit with a gamma curve
+        #[cfg(feature = "stbi_no_linear")]
+        F32 => {}
+    }
+}
+
+#[test]
+fn decode_conformance_fixtures() {
+    for case in CASES {
+        if !case.format.is_enabled() {
+            continue;
+        }
+
+        // Name the fixture in the output of a failing test
+        println!("{}", case.file);
+
+        let data = fixture(case.file);
+        check_info(case, &data);
+        check_pixels(case, &data);
+    }
+}
+
+/// Pixel of the pattern JPEG fixtures are encoded from
+#[cfg(not(feature = "stbi_no_jpeg"))]
+fn pattern(x: usize, y: usize) -> [u8; 3] {
+    [
+        (x * 17 + y * 2) as u8,
+        (y * 21 + x * 3) as u8,
+        (200 - x * 7 - y * 6) as u8,
+    ]
+}
+
+/// Decodes JPEG `file` from memory and from reader and checks every sample is within
+/// `tolerance` of the source pattern, returns the decoded image
+#[cfg(not(feature = "stbi_no_jpeg"))]
+fn check_jpeg(file: &'static str, components: usize, tolerance: u8) -> Image<u8> {
+    let data = fixture(file);
+    let case = case(file, ImageFormat::Jpeg, 13, 11, components, U8, 0);
+    check_info(&case, &data);
+
+    let decoded = decode(
+        &case,
+        &data,
+        |data| image::stbi_load_from_memory(data, Channels::Default, LoadOptions::default()),
+        |reader| image::stbi_load_from_reader(reader, Channels::Default, LoadOptions::default()),
+    );
+
+    for (y, row) in decoded.rows().enumerate() {
+        for (x, pixel) in row.chunks_exact(components).enumerate() {
+            let [r, g, b] = pattern(x, y);
+            let expected = if components == 1 {
+                // JFIF luma of the pattern
+                vec![
+                    (0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b)).round()
+                        as u8,
+                ]
+            } else {
+                vec![r, g, b]
+            };
+
+            for (sample, expected) in pixel.iter().zip(&expected) {
+                assert!(
+                    sample.abs_diff(*expected) <= tolerance,
+                    "{} at {}x{}: {} is not within {} of {}",
+                    file,
+                    x,
+                    y,
+                    sample,
+                    tolerance,
+                    expected
+                );
+            }
+        }
+    }
+
+    decoded
+}
+
+#[test]
+#[cfg(not(feature = "stbi_no_jpeg"))]
+fn decode_conformance_jpeg() {
+    let baseline = check_jpeg("jpeg-baseline-444.jpg", 3, 8);
+    // Both are encoded from the same coefficients, so progressive decoding must be exact
+    let progressive = check_jpeg("jpeg-progressive-444.jpg", 3, 8);
+    assert_eq!(baseline.as_slice(), progressive.as_slice());
+
+    // Chroma subsampling loses detail of the strong chroma gradients
+    check_jpeg("jpeg-baseline-420.jpg", 3, 24);
+    check_jpeg("jpeg-gray.jpg", 1, 4);
+}
@@ -0,0 +1,192 @@
+//! Entry points exercised by fuzz targets in `fuzz/` and by the regression corpus in
+//! `tests/fixtures/fuzz`, shared so both run exactly the same code.
+//!
+//! Malformed inputs are expected to fail with errors, only crashes, hangs and panics (other
+//! than the ones injected by `AdversarialReader`) are bugs.
+
+#![allow(dead_code)]
+
+use stb::image::{self, Channels, Limits, LoadOptions};
+use std::io::{self, Read};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Panic payload of `AdversarialReader`
+pub const READER_PANIC: &str = "adversarial reader panic";
+
+/// Keeps decoded images reasonably small, so huge dimensions declared by headers don't make
+/// fuzzers run out of memory
+fn options() -> LoadOptions {
+    LoadOptions::new().limits(Limits {
+        max_pixels: Some(1 << 22),
+        max_alloc_bytes: Some(1 << 26),
+        ..Limits::default()
+    })
+}
+
+/// Decodes `data` with memory loaders and metadata parsers
+pub fn load_memory(data: &[u8]) {
+    let _ = image::detect_format(data);
+    let _ = image::stbi_info_from_memory(data);
+    let _ = image::stbi_is_16_bit_from_memory(data);
+    let _ = image::stbi_is_hdr_from_memory(data);
+
+    let _ = image::stbi_load_from_memory(data, Channels::RgbAlpha, options());
+    let _ = image::stbi_load_16_from_memory(data, Channels::Default, options());
+    let _ = image::load_dynamic(data, Channels::Default, options().flip_vertically(true));
+
+    #[cfg(not(feature = "stbi_no_linear"))]
+    let _ = image::stbi_loadf_from_memory(data, Channels::Rgb, options());
+
+    #[cfg(not(feature = "stbi_no_gif"))]
+    let _ = image::stbi_load_gif_from_memory(data, Channels::Default, options());
+
+    let _ = image::color_info_from_memory(data);
+    let _ = image::jpeg_orientation(data);
+
+    #[cfg(not(feature = "stbi_no_png"))]
+    let _ = image::png_text_from_memory(data);
+}
+
+/// Decodes `input` with reader loaders, the leading bytes of `input` configure the reader
+/// (see `AdversarialReader::new`)
+pub fn load_reader(input: &[u8]) {
+    run_reader(input, |reader| {
+        if let Ok((_, mut reader)) = image::detect_format_from_reader(reader) {
+            let _ = image::stbi_info_from_reader(&mut reader);
+        }
+    });
+    run_reader(input, |reader| {
+        let _ = image::stbi_info_from_reader(reader);
+    });
+    run_reader(input, |reader| {
+        let _ = image::stbi_is_16_bit_from_reader(reader);
+    });
+    run_reader(input, |reader| {
+        let _ = image::stbi_load_from_reader(reader, Channels::RgbAlpha, options());
+    });
+    run_reader(input, |reader| {
+        let _ = image::load_dynamic_from_reader(reader, Channels::Default, options());
+    });
+    run_reader(input, |reader| {
+        let options = options().apply_orientation(true);
+        let _ = image::stbi_load_16_from_reader(reader, Channels::Default, options);
+    });
+}
+
+/// Runs `f` with a fresh reader for `input`, only the panic injected by the reader may escape
+fn run_reader(input: &[u8], f: impl FnOnce(&mut AdversarialReader)) {
+    let mut reader = AdversarialReader::new(input);
+
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| f(&mut reader))) {
+        match payload.downcast_ref::<&str>() {
+            Some(&READER_PANIC) => {}
+            _ => panic::resume_unwind(payload),
+        }
+    }
+}
+
+/// Inflates `data` as zlib and raw deflate streams
+#[cfg(any(not(feature = "stbi_no_png"), feature = "stbi_support_zlib"))]
+pub fn zlib_inflate(data: &[u8]) {
+    use stb::zlib;
+
+    let _ = zlib::inflate(data);
+    let _ = zlib::inflate_raw(data);
+
+    // Output buffers too small for most streams
+    let mut output = [0_u8; 64];
+    let _ = zlib::inflate_into(data, &mut output);
+    let _ = zlib::inflate_raw_into(data, &mut output);
+}
+
+/// How `AdversarialReader` misbehaves once it served `fail_after` bytes
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Failure {
+    None,
+    Error,
+    Panic,
+    Eof,
+}
+
+/// Reader serving data in chunks of varying size, interrupting reads and failing in the middle
+/// of the stream
+pub struct AdversarialReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Largest number of bytes served by a single read
+    chunk: usize,
+    /// Interrupts every n-th read, never if zero
+    interrupt_every: usize,
+    failure: Failure,
+    fail_after: usize,
+    /// Reads since the last interrupted one
+    reads: usize,
+}
+
+impl<'a> AdversarialReader<'a> {
+    /// Creates reader configured by the first two bytes of `input`, serving the rest of it.
+    ///
+    /// Bits of the first byte: 0-2 chunk size (power of two up to 128 bytes), 3-4 interrupt
+    /// every (n + 1)-th read unless zero, 5-6 failure (none, error, panic, early EOF).
+    /// The second byte is the failure offset in 16 byte units.
+    pub fn new(input: &'a [u8]) -> Self {
+        let (config, data) = match input {
+            [a, b, data @ ..] => ([*a, *b], data),
+            _ => ([0, 0], input),
+        };
+
+        let interrupt = ((config[0] >> 3) & 0b11) as usize;
+        let failure = match (config[0] >> 5) & 0b11 {
+            0 => Failure::None,
+            1 => Failure::Error,
+            2 => Failure::Panic,
+            _ => Failure::Eof,
+        };
+
+        AdversarialReader {
+            data,
+            pos: 0,
+            chunk: 1 << (config[0] & 0b111),
+            interrupt_every: if interrupt == 0 { 0 } else { interrupt + 1 },
+            failure,
+            fail_after: config[1] as usize * 16,
+            reads: 0,
+        }
+    }
+
+    /// Misbehaves if the failure offset is reached
+    fn fail(&self) -> Option<io::Result<usize>> {
+        if self.pos < self.fail_after {
+            return None;
+        }
+
+        match self.failure {
+            Failure::None => None,
+            Failure::Error => Some(Err(io::Error::other("adversarial reader error"))),
+            Failure::Panic => panic::panic_any(READER_PANIC),
+            Failure::Eof => Some(Ok(0)),
+        }
+    }
+}
+
+impl Read for AdversarialReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.interrupt_every != 0 {
+            self.reads += 1;
+            if self.reads == self.interrupt_every {
+                self.reads = 0;
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+        }
+
+        if let Some(result) = self.fail() {
+            return result;
+        }
+
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let n = buf.len().min(self.chunk).min(rest.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
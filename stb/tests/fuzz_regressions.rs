@@ -0,0 +1,46 @@
+//! Runs inputs from `tests/fixtures/fuzz/<target>` through the entry points of the matching
+//! fuzz target, so crashes found by fuzzing stay fixed. Minimized crashers found with
+//! `cargo fuzz` are added to the corpus as is.
+
+#![cfg(feature = "stb_image")]
+
+mod common;
+mod fuzz;
+
+use std::fs;
+
+/// Runs `f` for every input of fuzz `target`, returns the number of inputs
+fn run_corpus(target: &str, f: impl Fn(&[u8])) -> usize {
+    let dir = common::fixture_path("fuzz").join(target);
+
+    let mut paths = fs::read_dir(&dir)
+        .expect("Failed to read corpus directory")
+        .map(|entry| entry.expect("Failed to read corpus entry").path())
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    for path in &paths {
+        let data = fs::read(path).expect("Failed to read corpus file");
+        // Name the input in the output of a failing test
+        println!("{}", path.display());
+        f(&data);
+    }
+
+    paths.len()
+}
+
+#[test]
+fn load_memory_corpus() {
+    assert_ne!(run_corpus("load_memory", fuzz::load_memory), 0);
+}
+
+#[test]
+fn load_reader_corpus() {
+    assert_ne!(run_corpus("load_reader", fuzz::load_reader), 0);
+}
+
+#[test]
+#[cfg(any(not(feature = "stbi_no_png"), feature = "stbi_support_zlib"))]
+fn zlib_inflate_corpus() {
+    assert_ne!(run_corpus("zlib_inflate", fuzz::zlib_inflate), 0);
+}